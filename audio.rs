@@ -0,0 +1,212 @@
+use std::sync::{Arc, Condvar, Mutex};
+use std::time::Instant;
+use cpal::traits::{DeviceTrait, HostTrait, StreamTrait};
+use cpal::{FromSample, SizedSample};
+use crate::beat::Detector;
+use crate::ltc::{Chase, Decoder, Ltc};
+use crate::reclock;
+
+// how hard a callback's arrival time pulls on the anchor, callbacks wake up late by varying amounts
+const ALPHA: f64 = 0.02;
+// further off than this is a glitch in the stream, start over
+const GLITCH: f64 = 0.05;
+
+// the sound card's sample counter mapped onto Instant, advanced by the stream callback
+pub struct AudioClock {
+    // smoothed time of `frame`, frame, sample rate
+    anchor: Mutex<Option<(Instant, u64, f64)>>,
+    // frames handed to the sound card so far, and the clock thread waiting on them
+    rendered: Mutex<u64>,
+    reached: Condvar,
+}
+
+impl AudioClock {
+    pub fn new() -> Self {
+        Self { anchor: Mutex::new(None), rendered: Mutex::new(0), reached: Condvar::new() }
+    }
+
+    // `frame` is the number of frames rendered before the current callback, which
+    // came `now`. returns its smoothed time
+    fn advance(&self, frame: u64, rate: f64, now: Instant) -> Option<Instant> {
+        // never block the audio callback, the next one will catch up
+        let Ok(mut anchor) = self.anchor.try_lock() else {
+            return None;
+        };
+        *anchor = Some(match *anchor {
+            Some((t, f, _)) if frame >= f => {
+                let predicted = reclock::shift(t, (frame - f) as f64 / rate);
+                let err = reclock::secs_between(predicted, now);
+                if err.abs() > GLITCH {
+                    (now, frame, rate)
+                } else {
+                    (reclock::shift(predicted, err * ALPHA), frame, rate)
+                }
+            }
+            _ => (now, frame, rate),
+        });
+        anchor.map(|(t, _, _)| t)
+    }
+
+    // the callback has the frames up to `end`. a clock thread checking right now
+    // misses this one and gets woken by the next
+    fn render(&self, end: u64) {
+        if let Ok(mut rendered) = self.rendered.try_lock() {
+            *rendered = end;
+            self.reached.notify_all();
+        }
+    }
+
+    // blocks until the callback is rendering the buffer that holds `frame`, or
+    // `deadline` passes because the stream stalled or went away
+    pub fn wait_frame(&self, frame: f64, deadline: Instant) -> bool {
+        let mut rendered = self.rendered.lock().unwrap();
+        while *rendered as f64 <= frame {
+            let now = Instant::now();
+            if now >= deadline || self.anchor.lock().unwrap().is_none() {
+                return false;
+            }
+            rendered = self.reached.wait_timeout(rendered, deadline - now).unwrap().0;
+        }
+        true
+    }
+
+    pub fn stop(&self) {
+        *self.anchor.lock().unwrap() = None;
+        *self.rendered.lock().unwrap() = 0;
+        self.reached.notify_all();
+    }
+
+    pub fn rate(&self) -> Option<f64> {
+        self.anchor.lock().unwrap().map(|(_, _, rate)| rate)
+    }
+
+    pub fn instant_at(&self, frame: f64) -> Option<Instant> {
+        self.anchor
+            .lock()
+            .unwrap()
+            .map(|(t, f, rate)| reclock::shift(t, (frame - f as f64) / rate))
+    }
+
+    pub fn frame_at(&self, t: Instant) -> Option<f64> {
+        self.anchor
+            .lock()
+            .unwrap()
+            .map(|(at, f, rate)| f as f64 + reclock::secs_between(at, t) * rate)
+    }
+}
+
+// plays silence on the default output, to have its callback counting samples,
+// and LTC when it's switched on
+pub fn start_output(clock: Arc<AudioClock>, ltc: Arc<Mutex<Ltc>>) -> Result<cpal::Stream, String> {
+    let host = cpal::default_host();
+    let device = host.default_output_device().ok_or("No audio output device")?;
+    let config = device.default_output_config().map_err(|e| e.to_string())?;
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => render::<f32>(&device, &config.config(), clock, ltc),
+        cpal::SampleFormat::I16 => render::<i16>(&device, &config.config(), clock, ltc),
+        cpal::SampleFormat::U16 => render::<u16>(&device, &config.config(), clock, ltc),
+        other => return Err(format!("Unsupported sample format {:?}", other)),
+    }
+    .map_err(|e| e.to_string())?;
+    stream.play().map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+fn render<T: SizedSample + FromSample<f32>>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    clock: Arc<AudioClock>,
+    ltc: Arc<Mutex<Ltc>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError> {
+    let rate = config.sample_rate.0 as f64;
+    let channels = config.channels as usize;
+    let mut frames = 0u64;
+    device.build_output_stream(
+        config,
+        move |data: &mut [T], _: &cpal::OutputCallbackInfo| {
+            let at = clock.advance(frames, rate, Instant::now());
+            data.fill(T::EQUILIBRIUM);
+            frames += (data.len() / channels) as u64;
+            clock.render(frames);
+
+            let Ok(mut ltc) = ltc.try_lock() else {
+                return;
+            };
+            let Some(source) = ltc.source.clone() else {
+                return;
+            };
+            // song time of the first frame in this buffer
+            let origin = source.origin.try_lock().ok().and_then(|o| *o);
+            let start = origin.zip(at).map(|(origin, at)| reclock::secs_between(origin, at));
+            let channel = ltc.channel.min(channels - 1);
+            for (i, frame) in data.chunks_mut(channels).enumerate() {
+                let sample = ltc.sample(start.map(|t| t + i as f64 / rate));
+                frame[channel] = T::from_sample(sample);
+            }
+        },
+        |e| eprintln!("Audio stream error: {}", e),
+        None,
+    )
+}
+
+// listens to the default input for LTC and feeds what it decodes to the chase
+// one stream on the default input feeds both the LTC reader and beat detection
+pub fn start_input(chase: Arc<Mutex<Chase>>, beats: Arc<Mutex<Detector>>) -> Result<cpal::Stream, String> {
+    let host = cpal::default_host();
+    let device = host.default_input_device().ok_or("No audio input device")?;
+    let config = device.default_input_config().map_err(|e| e.to_string())?;
+    let stream = match config.sample_format() {
+        cpal::SampleFormat::F32 => listen::<f32>(&device, &config.config(), chase, beats),
+        cpal::SampleFormat::I16 => listen::<i16>(&device, &config.config(), chase, beats),
+        cpal::SampleFormat::U16 => listen::<u16>(&device, &config.config(), chase, beats),
+        other => return Err(format!("Unsupported sample format {:?}", other)),
+    }
+    .map_err(|e| e.to_string())?;
+    stream.play().map_err(|e| e.to_string())?;
+    Ok(stream)
+}
+
+fn listen<T: SizedSample>(
+    device: &cpal::Device,
+    config: &cpal::StreamConfig,
+    chase: Arc<Mutex<Chase>>,
+    beats: Arc<Mutex<Detector>>,
+) -> Result<cpal::Stream, cpal::BuildStreamError>
+where
+    f32: FromSample<T>,
+{
+    let rate = config.sample_rate.0 as f64;
+    let channels = config.channels as usize;
+    let mut decoder = Decoder::new(rate);
+    let mut channel = 0;
+    beats.lock().unwrap().reset(rate);
+    device.build_input_stream(
+        config,
+        move |data: &[T], _: &cpal::InputCallbackInfo| {
+            // the buffer has just been captured, its last frame is about now
+            let now = Instant::now();
+            let len = data.len() / channels;
+            if let Ok(chase) = chase.try_lock() {
+                channel = chase.channel.min(channels - 1);
+            }
+            for (i, frame) in data.chunks(channels).enumerate() {
+                if let Some((timecode, drop)) = decoder.sample(T::to_sample::<f32>(frame[channel])) {
+                    let at = reclock::shift(now, -((len - i) as f64 / rate));
+                    if let Ok(mut chase) = chase.try_lock() {
+                        chase.frame(at, timecode, decoder.fps(drop));
+                    }
+                }
+            }
+            if let Ok(mut beats) = beats.try_lock()
+                && beats.enabled
+            {
+                for frame in data.chunks(channels) {
+                    let mix = frame.iter().map(|&s| T::to_sample::<f32>(s)).sum::<f32>();
+                    beats.sample(mix / channels as f32);
+                }
+            }
+        },
+        |e| eprintln!("Audio input error: {}", e),
+        None,
+    )
+}
@@ -0,0 +1,144 @@
+use eframe::egui;
+use crate::clock::BEATS_PER_BAR;
+
+pub struct Point {
+    pub beat: f64,
+    pub bpm: f64,
+    // glide from the previous point instead of jumping on arrival
+    pub ramp: bool,
+}
+
+pub struct Automation {
+    pub points: Vec<Point>,
+    pub enabled: bool,
+    pub looping: bool,
+    pub bars: u32,
+}
+
+impl Automation {
+    pub fn new() -> Self {
+        Self { points: Vec::new(), enabled: false, looping: false, bars: 16 }
+    }
+
+    pub fn beats(&self) -> f64 {
+        self.bars as f64 * BEATS_PER_BAR as f64
+    }
+
+    pub fn active(&self) -> bool {
+        self.enabled && !self.points.is_empty()
+    }
+
+    pub fn tempo_at(&self, beat: f64) -> Option<f64> {
+        let next = self.points.iter().position(|p| p.beat > beat);
+        match next {
+            None => self.points.last().map(|p| p.bpm),
+            // hold the first tempo until its point
+            Some(0) => Some(self.points[0].bpm),
+            Some(i) => {
+                let (a, b) = (&self.points[i - 1], &self.points[i]);
+                if b.ramp {
+                    let t = (beat - a.beat) / (b.beat - a.beat);
+                    Some(a.bpm + (b.bpm - a.bpm) * t)
+                } else {
+                    Some(a.bpm)
+                }
+            }
+        }
+    }
+
+    pub fn sort(&mut self) {
+        self.points.sort_by(|a, b| a.beat.total_cmp(&b.beat));
+    }
+
+    // sorts and returns where the point at `beat` ended up
+    fn insert(&mut self, point: Point) -> usize {
+        let beat = point.beat;
+        self.points.retain(|p| p.beat != beat);
+        self.points.push(point);
+        self.sort();
+        self.points.iter().position(|p| p.beat == beat).unwrap()
+    }
+}
+
+// click adds a point, drag moves it, right click removes it. the view spans the
+// BPM range, points are clamped to it
+pub fn timeline(
+    ui: &mut egui::Ui,
+    automation: &mut Automation,
+    grabbed: &mut Option<usize>,
+    beat: f64,
+    (lo, hi): (f64, f64),
+) {
+    let size = egui::vec2(ui.available_width(), 160.0);
+    let (response, painter) = ui.allocate_painter(size, egui::Sense::click_and_drag());
+    let rect = response.rect;
+    let beats = automation.beats();
+
+    let to_screen = |beat: f64, bpm: f64| {
+        egui::pos2(
+            rect.left() + (beat / beats) as f32 * rect.width(),
+            rect.bottom() - ((bpm - lo) / (hi - lo)) as f32 * rect.height(),
+        )
+    };
+    let from_screen = |pos: egui::Pos2| {
+        let beat = ((pos.x - rect.left()) / rect.width()) as f64 * beats;
+        let bpm = lo + ((rect.bottom() - pos.y) / rect.height()) as f64 * (hi - lo);
+        (beat.round().clamp(0.0, beats), bpm.round().clamp(lo, hi))
+    };
+
+    painter.rect_filled(rect, 2.0, egui::Color32::from_gray(30));
+    for bar in 0..=automation.bars {
+        let x = to_screen(bar as f64 * BEATS_PER_BAR as f64, lo).x;
+        painter.line_segment(
+            [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+            egui::Stroke::new(1.0, egui::Color32::from_gray(60)),
+        );
+    }
+    if !automation.points.is_empty() {
+        let curve = (0..=400)
+            .map(|i| {
+                let b = beats * i as f64 / 400.0;
+                to_screen(b, automation.tempo_at(b).unwrap())
+            })
+            .collect();
+        painter.add(egui::Shape::line(curve, egui::Stroke::new(2.0, egui::Color32::LIGHT_BLUE)));
+    }
+    for point in &automation.points {
+        let color = if point.ramp { egui::Color32::ORANGE } else { egui::Color32::WHITE };
+        painter.circle_filled(to_screen(point.beat, point.bpm), 4.0, color);
+    }
+    let x = to_screen(beat.min(beats), lo).x;
+    painter.line_segment(
+        [egui::pos2(x, rect.top()), egui::pos2(x, rect.bottom())],
+        egui::Stroke::new(1.0, egui::Color32::RED),
+    );
+
+    let Some(pos) = response.interact_pointer_pos() else {
+        return;
+    };
+    let near = automation.points.iter().position(|p| {
+        let screen = to_screen(p.beat, p.bpm);
+        (screen.x - pos.x).abs() < 8.0 && (screen.y - pos.y).abs() < 8.0
+    });
+    let (beat, bpm) = from_screen(pos);
+
+    if response.secondary_clicked() {
+        if let Some(index) = near {
+            automation.points.remove(index);
+        }
+    } else if response.drag_started() {
+        *grabbed = near;
+    } else if response.dragged() {
+        // don't run over a neighbour while dragging
+        let taken = |index| automation.points.iter().enumerate().any(|(i, p)| i != index && p.beat == beat);
+        if let Some(index) = grabbed.filter(|&index| !taken(index)) {
+            let ramp = automation.points.remove(index).ramp;
+            *grabbed = Some(automation.insert(Point { beat, bpm, ramp }));
+        }
+    } else if response.clicked() && near.is_none() {
+        automation.insert(Point { beat, bpm, ramp: false });
+    }
+    if response.drag_stopped() {
+        *grabbed = None;
+    }
+}
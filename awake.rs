@@ -0,0 +1,88 @@
+use std::process::Child;
+
+// keeps the machine and display from sleeping while held. Windows takes a flag on
+// the calling thread, macOS and Linux a helper process that holds the inhibition
+// until it's killed
+pub struct Awake {
+    held: bool,
+    #[cfg_attr(windows, allow(dead_code))]
+    child: Option<Child>,
+}
+
+impl Awake {
+    pub fn new() -> Self {
+        Self { held: false, child: None }
+    }
+
+    pub fn set(&mut self, on: bool) {
+        if on == self.held {
+            return;
+        }
+        self.held = on;
+        if on {
+            self.hold();
+        } else {
+            self.release();
+        }
+    }
+
+    #[cfg(windows)]
+    fn hold(&mut self) {
+        const ES_CONTINUOUS: u32 = 0x8000_0000;
+        const ES_SYSTEM_REQUIRED: u32 = 0x0000_0001;
+        const ES_DISPLAY_REQUIRED: u32 = 0x0000_0002;
+        // SAFETY: takes a flag set and returns the previous one, nothing else
+        if unsafe { SetThreadExecutionState(ES_CONTINUOUS | ES_SYSTEM_REQUIRED | ES_DISPLAY_REQUIRED) } == 0 {
+            eprintln!("Failed to keep the system awake");
+        }
+    }
+
+    #[cfg(windows)]
+    fn release(&mut self) {
+        // SAFETY: as above
+        unsafe { SetThreadExecutionState(0x8000_0000) };
+    }
+
+    // both helpers also quit if we die without releasing: caffeinate watches our pid,
+    // cat under systemd-inhibit sees its stdin close
+    #[cfg(not(windows))]
+    fn hold(&mut self) {
+        use std::process::{Command, Stdio};
+        let pid = std::process::id().to_string();
+        let mut command = if cfg!(target_os = "macos") {
+            let mut c = Command::new("caffeinate");
+            c.args(["-d", "-i", "-w", &pid]);
+            c
+        } else {
+            let mut c = Command::new("systemd-inhibit");
+            c.args(["--what=idle:sleep", "--who=midiclock", "--why=MIDI clock running"]);
+            c.arg("cat");
+            c
+        };
+        match command.stdin(Stdio::piped()).stdout(Stdio::null()).stderr(Stdio::null()).spawn() {
+            Ok(child) => self.child = Some(child),
+            Err(e) => eprintln!("Failed to keep the system awake: {}", e),
+        }
+    }
+
+    #[cfg(not(windows))]
+    fn release(&mut self) {
+        if let Some(mut child) = self.child.take() {
+            drop(child.stdin.take());
+            let _ = child.kill();
+            let _ = child.wait();
+        }
+    }
+}
+
+impl Drop for Awake {
+    fn drop(&mut self) {
+        self.set(false);
+    }
+}
+
+#[cfg(windows)]
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn SetThreadExecutionState(flags: u32) -> u32;
+}
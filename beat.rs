@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+
+// the onset envelope runs at 100 Hz
+const HOP_SECS: f64 = 0.01;
+// seconds of envelope the tempo is estimated from
+const HISTORY: usize = 800;
+// re-estimate twice a second
+const EVERY: usize = 50;
+const MIN_BPM: f64 = 60.0;
+const MAX_BPM: f64 = 200.0;
+// bands mostly play near here, tips the half/double tempo ambiguity
+const PRIOR_BPM: f64 = 120.0;
+// a new estimate this close to the last one is smoothed into it
+const SMOOTH: f64 = 0.2;
+
+// onsets from the rise in high-passed energy, tempo from the autocorrelation of those
+pub struct Detector {
+    pub enabled: bool,
+    // pull the tab's tempo toward what's heard
+    pub steer: bool,
+    pub bpm: Option<f64>,
+    // height of the winning autocorrelation peak, 0 to 1
+    pub confidence: f64,
+    hop: usize,
+    count: usize,
+    energy: f64,
+    last_sample: f32,
+    last_level: f64,
+    envelope: VecDeque<f32>,
+    since: usize,
+}
+
+impl Detector {
+    pub fn new() -> Self {
+        Self {
+            enabled: false,
+            steer: false,
+            bpm: None,
+            confidence: 0.0,
+            hop: 0,
+            count: 0,
+            energy: 0.0,
+            last_sample: 0.0,
+            last_level: 0.0,
+            envelope: VecDeque::new(),
+            since: 0,
+        }
+    }
+
+    // the input stream starting, at its sample rate
+    pub fn reset(&mut self, rate: f64) {
+        *self = Self { enabled: self.enabled, steer: self.steer, ..Self::new() };
+        self.hop = (rate * HOP_SECS).round() as usize;
+    }
+
+    // one mono sample, true when it brought a new estimate
+    pub fn sample(&mut self, s: f32) -> bool {
+        if self.hop == 0 {
+            return false;
+        }
+        // first difference, drums and plucks stand out over sustained bass
+        let d = (s - self.last_sample) as f64;
+        self.last_sample = s;
+        self.energy += d * d;
+        self.count += 1;
+        if self.count < self.hop {
+            return false;
+        }
+        let level = (self.energy / self.count as f64 + 1e-10).ln();
+        self.envelope.push_back((level - self.last_level).max(0.0) as f32);
+        self.last_level = level;
+        self.energy = 0.0;
+        self.count = 0;
+        if self.envelope.len() > HISTORY {
+            self.envelope.pop_front();
+        }
+        self.since += 1;
+        if self.since >= EVERY && self.envelope.len() == HISTORY {
+            self.since = 0;
+            self.estimate();
+            return true;
+        }
+        false
+    }
+
+    fn estimate(&mut self) {
+        let mean = self.envelope.iter().sum::<f32>() as f64 / HISTORY as f64;
+        let x: Vec<f64> = self.envelope.iter().map(|&v| v as f64 - mean).collect();
+        let zero: f64 = x.iter().map(|v| v * v).sum();
+        if zero <= 0.0 {
+            self.bpm = None;
+            return;
+        }
+        let lag_of = |bpm: f64| 60.0 / bpm / HOP_SECS;
+        let (lo, hi) = (lag_of(MAX_BPM).floor() as usize, lag_of(MIN_BPM).ceil() as usize);
+        let corr: Vec<f64> = (lo - 1..=hi + 1)
+            .map(|lag| x.iter().zip(&x[lag..]).map(|(a, b)| a * b).sum::<f64>() / zero)
+            .collect();
+        let weighted = |i: usize| {
+            let bpm = 60.0 / ((lo - 1 + i) as f64 * HOP_SECS);
+            let octaves = (bpm / PRIOR_BPM).log2();
+            corr[i] * (-octaves * octaves).exp()
+        };
+        let Some(best) = (1..corr.len() - 1).max_by(|&a, &b| weighted(a).total_cmp(&weighted(b))) else {
+            return;
+        };
+        self.confidence = corr[best].clamp(0.0, 1.0);
+        if corr[best] <= 0.0 {
+            self.bpm = None;
+            return;
+        }
+        // parabola through the peak for a lag between hops
+        let (a, b, c) = (corr[best - 1], corr[best], corr[best + 1]);
+        let shift = if a - 2.0 * b + c != 0.0 { 0.5 * (a - c) / (a - 2.0 * b + c) } else { 0.0 };
+        let bpm = 60.0 / ((lo - 1 + best) as f64 + shift.clamp(-0.5, 0.5)) / HOP_SECS;
+        self.bpm = Some(match self.bpm {
+            Some(last) if (bpm / last - 1.0).abs() < 0.05 => last + (bpm - last) * SMOOTH,
+            _ => bpm,
+        });
+    }
+}
+
+// a whole recording, the median of the estimates along it
+pub fn analyze(samples: &[f32], rate: f64) -> Result<f64, String> {
+    let mut detector = Detector::new();
+    detector.reset(rate);
+    let mut estimates = Vec::new();
+    for &s in samples {
+        if detector.sample(s) {
+            // independent windows, not the live smoothing
+            estimates.extend(detector.bpm.take());
+        }
+    }
+    if estimates.is_empty() {
+        return Err("Too short or no clear beat".to_string());
+    }
+    estimates.sort_by(f64::total_cmp);
+    Ok(estimates[estimates.len() / 2])
+}
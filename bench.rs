@@ -0,0 +1,100 @@
+use std::fs;
+use std::time::{Duration, Instant};
+use crate::clock::{wait_until, Sleep};
+use crate::config;
+
+const REPORT: &str = "midiclock-bench.txt";
+
+// lateness of every tick in microseconds
+struct Stats(Vec<f64>);
+
+impl Stats {
+    fn mean(&self) -> f64 {
+        self.0.iter().sum::<f64>() / self.0.len() as f64
+    }
+
+    fn sd(&self) -> f64 {
+        let mean = self.mean();
+        (self.0.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / self.0.len() as f64).sqrt()
+    }
+
+    // expects sorted samples
+    fn percentile(&self, p: f64) -> f64 {
+        let index = ((self.0.len() - 1) as f64 * p).round() as usize;
+        self.0[index]
+    }
+}
+
+// the same schedule the clock thread runs, with nothing on the other end
+fn run(sleep: Sleep, bpm: f64, length: Duration) -> Stats {
+    let interval = Duration::from_secs_f64(60.0 / (bpm * 24.0));
+    let start = Instant::now();
+    let mut next_tick = start + interval;
+    let mut late = Vec::new();
+    while next_tick - start < length {
+        wait_until(next_tick, sleep);
+        late.push((Instant::now() - next_tick).as_secs_f64() * 1_000_000.0);
+        next_tick += interval;
+    }
+    late.sort_by(f64::total_cmp);
+    Stats(late)
+}
+
+// `midiclock --bench [minutes] [--bpm N]`, runs every sleep strategy for that long
+pub fn main(args: &[String]) {
+    const USAGE: &str = "usage: midiclock --bench [minutes] [--bpm N], minutes above 0 and up to a day, BPM 1 to 999";
+    // NaN parses too, the ranges keep it out
+    let minutes = match args.get(2).filter(|a| *a != "--bpm") {
+        Some(a) => a.parse::<f64>().ok(),
+        None => Some(1.0),
+    }
+    .filter(|m| (f64::MIN_POSITIVE..=24.0 * 60.0).contains(m));
+    let bpm = match args.iter().position(|a| a == "--bpm") {
+        Some(i) => args.get(i + 1).and_then(|a| a.parse::<f64>().ok()),
+        None => Some(120.0),
+    }
+    .filter(|b| (1.0..=999.0).contains(b));
+    let (Some(minutes), Some(bpm)) = (minutes, bpm) else {
+        eprintln!("{}", USAGE);
+        return;
+    };
+    let length = Duration::from_secs_f64(minutes * 60.0);
+
+    let mut report = format!(
+        "midiclock timing benchmark\nplatform: {} {}, {} BPM, {} min per strategy\n\n{:<8} {:>8} {:>9} {:>9} {:>9} {:>9} {:>9}\n",
+        std::env::consts::OS,
+        std::env::consts::ARCH,
+        bpm,
+        minutes,
+        "strategy",
+        "ticks",
+        "mean us",
+        "sd us",
+        "p50 us",
+        "p99 us",
+        "max us",
+    );
+    for sleep in Sleep::ALL {
+        println!("running {} for {} min", sleep.name(), minutes);
+        let stats = run(sleep, bpm, length);
+        if stats.0.is_empty() {
+            continue;
+        }
+        report += &format!(
+            "{:<8} {:>8} {:>9.1} {:>9.1} {:>9.1} {:>9.1} {:>9.1}\n",
+            sleep.name(),
+            stats.0.len(),
+            stats.mean(),
+            stats.sd(),
+            stats.percentile(0.5),
+            stats.percentile(0.99),
+            stats.percentile(1.0),
+        );
+    }
+
+    print!("\n{}", report);
+    // the release build has no console on windows
+    if let Err(e) = fs::write(config::output(REPORT), &report) {
+        eprintln!("Failed to write {}: {}", REPORT, e);
+    }
+}
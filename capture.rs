@@ -0,0 +1,169 @@
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant, SystemTime};
+use crate::config;
+use crate::session;
+
+// keeps what's unwritten small if we crash mid-capture
+const FLUSH: Duration = Duration::from_millis(500);
+// the .mid counts SMPTE time, 25 frames of 40 ticks is a millisecond a tick
+const FRAMES: u8 = 25;
+const TICKS_PER_FRAME: u8 = 40;
+// what the .mid holds at most, hours of clock. the text file goes on
+const MAX_EVENTS: usize = 1_000_000;
+
+static ON: AtomicBool = AtomicBool::new(false);
+// the clock threads only hand events over, the writer thread formats and writes them
+static CAPTURE: Mutex<Option<Writer>> = Mutex::new(None);
+
+type Event = (Instant, usize, Vec<u8>);
+type Writer = (Sender<Event>, JoinHandle<Result<String, String>>);
+
+struct Capture {
+    name: String,
+    file: BufWriter<File>,
+    started: Instant,
+    flushed: Instant,
+    ports: Vec<String>,
+    // for the .mid written at the end, ms since the start
+    events: Vec<(u64, usize, Vec<u8>)>,
+}
+
+// every byte sent on any port from any tab, to midiclock-capture-<time>.txt where
+// exports go, as seconds, port and hex. returns the file name
+pub fn start(ports: Vec<String>) -> Result<String, String> {
+    let mut capture = CAPTURE.lock().unwrap();
+    if capture.is_some() {
+        return Err("Already capturing".to_string());
+    }
+    let now = SystemTime::now();
+    let stamp = session::timestamp(now).replace([':', '.'], "-");
+    let name = format!("midiclock-capture-{}.txt", stamp);
+    let file = File::create(config::output(&name)).map_err(|e| format!("Failed to create {}: {}", name, e))?;
+    let mut file = BufWriter::new(file);
+    let _ = writeln!(file, "# midiclock capture from {}", session::timestamp(now));
+    for (index, port) in ports.iter().enumerate().skip(1) {
+        let _ = writeln!(file, "# port {}: {}", index, port);
+    }
+    let _ = writeln!(file, "# seconds\tport\tbytes");
+    let writer = Capture { name: name.clone(), file, started: Instant::now(), flushed: Instant::now(), ports, events: Vec::new() };
+    let (tx, rx) = mpsc::channel();
+    let thread = thread::Builder::new()
+        .name("capture".to_string())
+        .spawn(move || write(writer, rx))
+        .map_err(|e| format!("Failed to start capture: {}", e))?;
+    *capture = Some((tx, thread));
+    ON.store(true, Ordering::SeqCst);
+    Ok(name)
+}
+
+// closes the text file and writes the same events next to it as a .mid, a track per
+// port. returns the .mid's name
+pub fn stop() -> Result<String, String> {
+    ON.store(false, Ordering::SeqCst);
+    let Some((tx, thread)) = CAPTURE.lock().unwrap().take() else {
+        return Err("Not capturing".to_string());
+    };
+    // the writer finishes up once the channel closes
+    drop(tx);
+    thread.join().unwrap_or_else(|_| Err("Capture writer failed".to_string()))
+}
+
+pub fn active() -> bool {
+    ON.load(Ordering::SeqCst)
+}
+
+// call with everything that goes out, right as it does. only queues it, the
+// timing threads never wait on the disk
+pub fn record(at: Instant, port: usize, msg: &[u8]) {
+    if !ON.load(Ordering::SeqCst) {
+        return;
+    }
+    if let Some((tx, _)) = CAPTURE.lock().unwrap().as_ref() {
+        let _ = tx.send((at, port, msg.to_vec()));
+    }
+}
+
+fn write(mut capture: Capture, rx: Receiver<Event>) -> Result<String, String> {
+    loop {
+        match rx.recv_timeout(FLUSH) {
+            Ok((at, port, msg)) => {
+                let at = at.saturating_duration_since(capture.started);
+                let hex: Vec<String> = msg.iter().map(|b| format!("{:02X}", b)).collect();
+                let _ = writeln!(capture.file, "{:.6}\t{}\t{}", at.as_secs_f64(), port, hex.join(" "));
+                if capture.events.len() < MAX_EVENTS {
+                    capture.events.push((at.as_millis() as u64, port, msg));
+                }
+            }
+            Err(RecvTimeoutError::Timeout) => {}
+            Err(RecvTimeoutError::Disconnected) => break,
+        }
+        if capture.flushed.elapsed() > FLUSH {
+            let _ = capture.file.flush();
+            capture.flushed = Instant::now();
+        }
+    }
+    capture.file.flush().map_err(|e| format!("Failed to write {}: {}", capture.name, e))?;
+    let name = capture.name.replace(".txt", ".mid");
+    fs::write(config::output(&name), smf(&capture)).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    Ok(name)
+}
+
+fn smf(capture: &Capture) -> Vec<u8> {
+    let mut ports: Vec<usize> = capture.events.iter().map(|&(_, port, _)| port).collect();
+    ports.sort();
+    ports.dedup();
+
+    let mut data = b"MThd".to_vec();
+    data.extend(6u32.to_be_bytes());
+    data.extend(1u16.to_be_bytes());
+    data.extend((ports.len() as u16).to_be_bytes());
+    data.extend([FRAMES.wrapping_neg(), TICKS_PER_FRAME]);
+    for port in ports {
+        let mut track = Vec::new();
+        let name = capture.ports.get(port).map_or("", String::as_str).as_bytes();
+        track.extend([0, 0xFF, 0x03]);
+        varlen(&mut track, name.len() as u64);
+        track.extend(name);
+        let mut last = 0;
+        for (at, _, msg) in capture.events.iter().filter(|e| e.1 == port) {
+            varlen(&mut track, at - last);
+            last = *at;
+            match msg[0] {
+                // a sysex event holds everything after the F0
+                0xF0 => {
+                    track.push(0xF0);
+                    varlen(&mut track, msg.len() as u64 - 1);
+                    track.extend(&msg[1..]);
+                }
+                // channel messages go in as they are
+                0x80..=0xEF => track.extend(msg),
+                // realtime and system common can only go in as escapes
+                _ => {
+                    track.push(0xF7);
+                    varlen(&mut track, msg.len() as u64);
+                    track.extend(msg);
+                }
+            }
+        }
+        track.extend([0, 0xFF, 0x2F, 0]);
+        data.extend(b"MTrk");
+        data.extend((track.len() as u32).to_be_bytes());
+        data.extend(track);
+    }
+    data
+}
+
+fn varlen(out: &mut Vec<u8>, value: u64) {
+    let mut bytes = vec![(value & 0x7F) as u8];
+    let mut rest = value >> 7;
+    while rest > 0 {
+        bytes.push((rest & 0x7F) as u8 | 0x80);
+        rest >>= 7;
+    }
+    out.extend(bytes.iter().rev());
+}
@@ -0,0 +1,1146 @@
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::thread::{self, JoinHandle};
+use std::time::{Instant, Duration, SystemTime};
+use crate::audio::AudioClock;
+use crate::capture;
+use crate::automation::Automation;
+use crate::crash::{self, Connections};
+use crate::cues::{Cue, Ramp};
+use crate::connect::{Ports, Sink};
+use crate::dmx::{self, Dmx};
+#[cfg(feature = "gpio")]
+use crate::gpio::{self, Gpio};
+use crate::lights::{self, Lights};
+use crate::ltc::Chase;
+use crate::metrics::Stats;
+use crate::mqtt::{self, Mqtt};
+use crate::msc;
+use crate::net::{self, Follower, Master};
+use crate::profile::Template;
+use crate::reclock::{self, Pll};
+use crate::schedule::{self, Timer};
+use crate::session::{self, Kind};
+use crate::testmode::{Humanize, Rng};
+use crate::time::Time;
+
+pub const BEATS_PER_BAR: u64 = 4;
+pub const TICKS_PER_BAR: u64 = BEATS_PER_BAR * 24;
+const NO_SEEK: u64 = u64::MAX;
+const MIDI_TICK: Duration = Duration::from_millis(10);
+// an armed start is placed on the grid once it's this close, ticks are shorter above 5 BPM
+const ARM_HORIZON: Duration = Duration::from_millis(500);
+// seconds past a tick without the audio callback reaching it before timing falls back to sleeps
+const AUDIO_STALL: f64 = 0.05;
+
+// where the clock thread takes its timing from
+#[derive(Clone, Copy, PartialEq)]
+pub enum Source {
+    Internal,
+    ExternalMidi,
+    // chases LTC on the audio input
+    Ltc,
+    // follows another instance's master tab on the LAN
+    Network,
+}
+
+impl Source {
+    pub fn load(shared: &AtomicUsize) -> Self {
+        match shared.load(Ordering::SeqCst) {
+            1 => Source::ExternalMidi,
+            2 => Source::Ltc,
+            3 => Source::Network,
+            _ => Source::Internal,
+        }
+    }
+
+    pub fn store(self, shared: &AtomicUsize) {
+        shared.store(self as usize, Ordering::SeqCst);
+    }
+}
+
+// how the thread waits for the next tick, plain sleeps are cheap but coarse on some systems
+#[derive(Clone, Copy, PartialEq)]
+pub enum Sleep {
+    Plain,
+    // sleep most of the way, then spin the last stretch
+    Hybrid,
+    Spin,
+}
+
+impl Sleep {
+    pub const ALL: [Sleep; 3] = [Sleep::Plain, Sleep::Hybrid, Sleep::Spin];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Sleep::Plain => "sleep",
+            Sleep::Hybrid => "hybrid",
+            Sleep::Spin => "spin",
+        }
+    }
+
+    pub fn load(shared: &AtomicUsize) -> Self {
+        Self::ALL.get(shared.load(Ordering::SeqCst)).copied().unwrap_or(Sleep::Plain)
+    }
+}
+
+pub fn wait_until(deadline: Instant, sleep: Sleep) {
+    let spin_from = match sleep {
+        Sleep::Plain => deadline,
+        Sleep::Hybrid => deadline - Duration::from_millis(2),
+        Sleep::Spin => Instant::now(),
+    };
+    let now = Instant::now();
+    if now < spin_from {
+        thread::sleep(spin_from - now);
+    }
+    while Instant::now() < deadline {
+        std::hint::spin_loop();
+    }
+}
+
+// master output offset, positive delays the whole clock
+pub struct Offset {
+    pub value: AtomicI32,
+    pub ticks: AtomicBool,
+}
+
+impl Offset {
+    // `tick` is the current tick interval in seconds
+    fn secs(&self, tick: f64) -> f64 {
+        let value = self.value.load(Ordering::SeqCst) as f64;
+        if self.ticks.load(Ordering::SeqCst) {
+            value * tick
+        } else {
+            value / 1000.0
+        }
+    }
+}
+
+// what the gui asks of the clock thread. one request carries everything that has to
+// change together, a start and where from, and they're applied in order on one loop
+#[derive(Clone, Copy)]
+pub enum Request {
+    // the manual tempo, whole BPM
+    Tempo(i32),
+    // the manual tempo between whole BPM, as kept from taps
+    Exact(f64),
+    // to a tempo over some beats while running, straight there while stopped
+    Glide(i32, u32),
+    // from a tick, 0 is the top with a plain Start, later ones relocate the slaves
+    Start(u64),
+    Stop,
+    // moves the transport, the slaves follow with song position
+    Locate(u64),
+}
+
+// a request and when it was made
+struct Timed {
+    at: Instant,
+    request: Request,
+}
+
+// one per output port, muting keeps the connection open
+pub struct Output {
+    pub enabled: AtomicBool,
+    pub muted: AtomicBool,
+    // 0xF9 every 10 ms instead of beat clock
+    pub midi_tick: AtomicBool,
+    // open, or on its way to the clock thread
+    pub connected: AtomicBool,
+    // from the port's device profile: clock only while running, beats of clock
+    // before Start, and how many clocks per quarter it counts
+    pub gate: AtomicBool,
+    pub preroll: AtomicU32,
+    pub ppqn: AtomicU32,
+    // sent when the port opens and before each start, to switch it into external sync
+    pub sysex: Mutex<Vec<Vec<u8>>>,
+    // exact tempo for devices that take it, sent when it changes
+    pub tempo_sysex: Mutex<Option<Template>>,
+}
+
+// state shared between the GUI and one clock thread
+pub struct Clock {
+    // the tab's name, for the session log
+    pub name: String,
+    pub bpm: AtomicI32,
+    // f64 bits of a manual tempo between whole BPM, it counts while `bpm` is its rounding
+    pub exact: AtomicU64,
+    // counts up around every change the thread makes to `bpm` by itself, so undo
+    // can tell cues, timers and finished glides from the user
+    pub moved: AtomicU64,
+    pub outputs: Vec<Output>,
+    pub source: AtomicUsize,
+    pub offset: Offset,
+    pub running: AtomicBool,
+    // held cut, ticks keep their schedule but aren't sent
+    pub cut: AtomicBool,
+    // fermata, time stands still while held and picks up where it was on release
+    pub hold: AtomicBool,
+    // and the slaves get Stop and Continue around it
+    pub hold_stop: AtomicBool,
+    // ticks sent since the last Start
+    pub position: AtomicU64,
+    // tempo the thread is actually running at, f64 bits
+    tempo: AtomicU64,
+    // pending jump in ticks, relocates the slaves with song position
+    seek: AtomicU64,
+    pub automation: Mutex<Automation>,
+    pub cues: Mutex<Vec<Cue>>,
+    pub humanize: Humanize,
+    pub sleep: AtomicUsize,
+    // most the internal tempo may move in BPM per second while running, 0 jumps
+    pub slew: AtomicU32,
+    // Slow stop asked for, and the beats it takes
+    pub slow_stop: AtomicBool,
+    pub slow_stop_beats: AtomicU32,
+    // bars after Start the transport stops by itself, 0 runs on
+    pub stop_after: AtomicU32,
+    // place ticks on the sound card's sample clock instead of Instant arithmetic
+    pub audio: AtomicBool,
+    pub dmx: Mutex<Dmx>,
+    pub lights: Mutex<Lights>,
+    #[cfg(feature = "gpio")]
+    pub gpio: Mutex<Gpio>,
+    pub mqtt: Mutex<Mqtt>,
+    pub net: Mutex<Master>,
+    pub stats: Stats,
+    pub msc: Mutex<msc::Settings>,
+    // when the song's start was, or would have been at the current tempo. None while stopped
+    pub origin: Mutex<Option<Instant>>,
+    // wall-clock time the transport starts by itself, dropped once running
+    pub armed: Mutex<Option<SystemTime>>,
+    pub timers: Mutex<Vec<Timer>>,
+    // SysEx from the gui, sent on the next loop
+    pub outbox: Mutex<Vec<Vec<u8>>>,
+    // the watchdog's view of the thread: which one is current, when it last went
+    // round its loop (ms since `born`) and its handle
+    generation: AtomicU64,
+    heartbeat: AtomicU64,
+    born: Instant,
+    thread: Mutex<Option<JoinHandle<()>>>,
+    requests: Sender<Timed>,
+    inbox: Mutex<Receiver<Timed>>,
+    // the idle thread waits on this instead of polling, the gui pokes it after changes
+    poked: Mutex<bool>,
+    wakeup: Condvar,
+    // pokes the gui when something it shows changed, so it needn't redraw on a timer
+    pub waker: Mutex<Option<Box<dyn Fn() + Send>>>,
+    // why the thread was last restarted, until the gui dismisses it
+    pub incident: Mutex<Option<String>>,
+    closed: AtomicBool,
+}
+
+impl Clock {
+    pub fn new(ports: usize, name: String) -> Self {
+        let outputs = (0..ports)
+            .map(|index| Output {
+                // port 0 is skipped, start out on the first real one
+                enabled: AtomicBool::new(index == 1),
+                muted: AtomicBool::new(false),
+                midi_tick: AtomicBool::new(false),
+                connected: AtomicBool::new(false),
+                gate: AtomicBool::new(false),
+                preroll: AtomicU32::new(0),
+                ppqn: AtomicU32::new(24),
+                sysex: Mutex::new(Vec::new()),
+                tempo_sysex: Mutex::new(None),
+            })
+            .collect();
+        let (requests, inbox) = mpsc::channel();
+        Self {
+            name: name.clone(),
+            bpm: AtomicI32::new(0),
+            exact: AtomicU64::new(0),
+            outputs,
+            source: AtomicUsize::new(Source::Internal as usize),
+            offset: Offset { value: AtomicI32::new(0), ticks: AtomicBool::new(false) },
+            running: AtomicBool::new(false),
+            cut: AtomicBool::new(false),
+            hold: AtomicBool::new(false),
+            hold_stop: AtomicBool::new(false),
+            position: AtomicU64::new(0),
+            tempo: AtomicU64::new(0),
+            moved: AtomicU64::new(0),
+            seek: AtomicU64::new(NO_SEEK),
+            automation: Mutex::new(Automation::new()),
+            cues: Mutex::new(Vec::new()),
+            humanize: Humanize::new(),
+            sleep: AtomicUsize::new(Sleep::Plain as usize),
+            slew: AtomicU32::new(0),
+            slow_stop: AtomicBool::new(false),
+            slow_stop_beats: AtomicU32::new(8),
+            stop_after: AtomicU32::new(0),
+            audio: AtomicBool::new(false),
+            dmx: Mutex::new(Dmx::new()),
+            lights: Mutex::new(Lights::new()),
+            #[cfg(feature = "gpio")]
+            gpio: Mutex::new(Gpio::new()),
+            stats: Stats::new(),
+            msc: Mutex::new(msc::Settings::new()),
+            outbox: Mutex::new(Vec::new()),
+            origin: Mutex::new(None),
+            armed: Mutex::new(None),
+            timers: Mutex::new(Vec::new()),
+            mqtt: Mutex::new(Mqtt::new(format!("midiclock/{}", name.to_lowercase().replace(' ', "")))),
+            net: Mutex::new(Master::new()),
+            generation: AtomicU64::new(0),
+            heartbeat: AtomicU64::new(0),
+            born: Instant::now(),
+            thread: Mutex::new(None),
+            requests,
+            inbox: Mutex::new(inbox),
+            poked: Mutex::new(false),
+            wakeup: Condvar::new(),
+            waker: Mutex::new(None),
+            incident: Mutex::new(None),
+            closed: AtomicBool::new(false),
+        }
+    }
+
+    pub fn tempo(&self) -> f64 {
+        f64::from_bits(self.tempo.load(Ordering::SeqCst))
+    }
+
+    fn move_bpm(&self, bpm: i32) {
+        self.moved.fetch_add(1, Ordering::SeqCst);
+        self.bpm.store(bpm, Ordering::SeqCst);
+        self.moved.fetch_add(1, Ordering::SeqCst);
+    }
+
+    // how long this clock has been up, across thread restarts
+    pub fn uptime(&self) -> Duration {
+        self.born.elapsed()
+    }
+
+    // the manual tempo, with its fraction if it has one
+    pub fn manual(&self) -> f64 {
+        let bpm = self.bpm.load(Ordering::SeqCst);
+        match f64::from_bits(self.exact.load(Ordering::SeqCst)) {
+            exact if exact.round() as i32 == bpm && exact > 0.0 => exact,
+            _ => bpm as f64,
+        }
+    }
+
+    pub fn seek(&self, tick: u64) {
+        self.seek.store(tick, Ordering::SeqCst);
+    }
+
+    // lets the thread finish, it closes its connections on the way out
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+        self.poke();
+    }
+
+    pub fn request(&self, request: Request) {
+        let _ = self.requests.send(Timed { at: Instant::now(), request });
+        self.poke();
+    }
+
+    // tempo, ports or transport changed, an idle thread looks again right away
+    pub fn poke(&self) {
+        *self.poked.lock().unwrap() = true;
+        self.wakeup.notify_all();
+    }
+
+    // sleeps until poked, or `timeout` for the port retries and the watchdog
+    pub fn idle(&self, timeout: Duration) {
+        let poked = self.poked.lock().unwrap();
+        let (mut poked, _) = self.wakeup.wait_timeout_while(poked, timeout, |p| !*p).unwrap();
+        *poked = false;
+    }
+
+    // puts the song's start where `position` would be now
+    fn locate(&self, position: u64, now: Instant) {
+        let tempo = self.tempo();
+        let secs = if tempo > 0.0 { position as f64 * 60.0 / (tempo * 24.0) } else { 0.0 };
+        *self.origin.lock().unwrap() = Some(reclock::shift(now, -secs));
+    }
+
+    fn wake(&self) {
+        if let Some(wake) = self.waker.lock().unwrap().as_ref() {
+            wake();
+        }
+    }
+
+    pub fn closed(&self) -> bool {
+        self.closed.load(Ordering::SeqCst)
+    }
+
+    fn beat(&self, now: Instant) {
+        self.heartbeat.store(now.saturating_duration_since(self.born).as_millis() as u64, Ordering::SeqCst);
+    }
+
+    pub fn since_heartbeat(&self) -> Duration {
+        let beat = Duration::from_millis(self.heartbeat.load(Ordering::SeqCst));
+        self.born.elapsed().saturating_sub(beat)
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    // the thread has returned or unwound
+    pub fn finished(&self) -> bool {
+        self.thread.lock().unwrap().as_ref().is_some_and(JoinHandle::is_finished)
+    }
+}
+
+// sends to every connected, unmuted output
+fn send(conns: &mut [Option<Box<dyn Sink>>], outputs: &[Output], time: &impl Time, msg: &[u8]) {
+    send_if(conns, outputs, time, msg, |_| true);
+}
+
+fn send_if(conns: &mut [Option<Box<dyn Sink>>], outputs: &[Output], time: &impl Time, msg: &[u8], want: impl Fn(&Output) -> bool) {
+    for (index, (conn, output)) in conns.iter_mut().zip(outputs).enumerate() {
+        if let Some(conn) = conn
+            && !output.muted.load(Ordering::SeqCst)
+            && want(output)
+        {
+            let _ = conn.send(msg);
+            capture::record(time.now(), index, msg);
+        }
+    }
+}
+
+fn midi_tick(output: &Output) -> bool {
+    output.midi_tick.load(Ordering::SeqCst)
+}
+
+// MIDI Tick keeps its own 10 ms beat whatever the tempo, through idling and holds
+// too. sends the ones due before `until`, waiting for each, and none while cut
+fn midi_ticks(conns: &mut [Option<Box<dyn Sink>>], clock: &Clock, time: &impl Time, next: &mut Instant, until: Instant) {
+    let now = time.now();
+    if !clock.outputs.iter().any(midi_tick) || now > *next + MIDI_TICK * 10 {
+        // nobody wanted them, or back from a stall: don't burst out the missed ones
+        *next = now;
+    }
+    if !clock.outputs.iter().any(midi_tick) {
+        return;
+    }
+    while *next < until {
+        time.wait_until(*next, Sleep::load(&clock.sleep));
+        if !clock.cut.load(Ordering::SeqCst) {
+            send_if(conns, &clock.outputs, time, &[0xF9], midi_tick);
+        }
+        *next += MIDI_TICK;
+    }
+}
+
+// how long a wait can be before the next MIDI Tick is due
+fn nap(clock: &Clock, time: &impl Time, next_midi_tick: Instant, longest: Duration) -> Duration {
+    match clock.outputs.iter().any(midi_tick) {
+        true => next_midi_tick.saturating_duration_since(time.now()).min(longest),
+        false => longest,
+    }
+}
+
+fn handshake(conn: &mut dyn Sink, index: usize, output: &Output, time: &impl Time) {
+    if output.muted.load(Ordering::SeqCst) {
+        return;
+    }
+    for msg in output.sysex.lock().unwrap().iter() {
+        let _ = conn.send(msg);
+        capture::record(time.now(), index, msg);
+    }
+}
+
+// clocks per 24 ppqn tick
+fn ticks(output: &Output) -> u32 {
+    output.ppqn.load(Ordering::SeqCst) / 24
+}
+
+// stop, move and continue the slaves, position is rounded down to a 16th
+fn relocate(conns: &mut [Option<Box<dyn Sink>>], outputs: &[Output], time: &impl Time, position: u64) {
+    let spp = position / 6;
+    send(conns, outputs, time, &[0xFC]);
+    send(conns, outputs, time, &[0xF2, (spp & 0x7F) as u8, ((spp >> 7) & 0x7F) as u8]);
+    send(conns, outputs, time, &[0xFB]);
+}
+
+// starts the clock's thread, or a replacement for a dead or wedged one. a
+// replacement picks the transport up where it was instead of restarting the song
+pub fn spawn(
+    clock: Arc<Clock>,
+    pll: Arc<Mutex<Pll>>,
+    chase: Arc<Mutex<Chase>>,
+    follower: Arc<Mutex<Follower>>,
+    audio: Arc<AudioClock>,
+    ports: impl Ports + 'static,
+    time: impl Time + 'static,
+) {
+    let generation = clock.generation.fetch_add(1, Ordering::SeqCst) + 1;
+    clock.beat(time.now());
+    let thread_clock = Arc::clone(&clock);
+    let handle = thread::Builder::new().name("clock".to_string()).spawn(move || {
+        let clock = thread_clock;
+        let mut conns = Connections::new(clock.outputs.len());
+        let opened = ports.open(Arc::clone(&clock), generation);
+        let mut next_tick = time.now();
+        let mut applied_offset = 0.0;
+        let mut was_running = false;
+        let mut was_cut = false;
+        let mut ramp: Option<Ramp> = None;
+        let mut fired = None;
+        let mut rng = Rng::new(time.system());
+        let mut wander = 0.0;
+        // test mode drift so far as a fraction, and when it was last added to
+        let mut drifted = 0.0;
+        let mut drift_at: Option<Instant> = None;
+        // next tick in audio frames while on audio timing
+        let mut next_frame: Option<f64> = None;
+        let mut logged_bpm = 0.0;
+        let mut dmx = dmx::Sender::new(time.system());
+        let flash = lights::spawn(Arc::clone(&clock));
+        #[cfg(feature = "gpio")]
+        let pulse = gpio::spawn(Arc::clone(&clock));
+        let publish = mqtt::spawn(Arc::clone(&clock));
+        // hundredths of a BPM, what MQTT last heard
+        let mut published = 0;
+        // whole BPM, what the gui last showed
+        let mut shown = 0;
+        let mut resuming = generation > 1 && clock.running.load(Ordering::SeqCst);
+        let mut next_midi_tick = time.now();
+        // the transport was started by incoming timecode or the network master
+        let mut chasing = false;
+        let mut network = net::Sender::new();
+        // Some while holding, with whether Stop went out for it
+        let mut held: Option<bool> = None;
+        // the tempo after slew limiting and when it was last moved
+        let mut slewed: Option<(f64, Instant)> = None;
+        // ticks of pre-roll sent since Start was asked for
+        let mut prerolled = 0;
+        // the tempo SysEx each port last got, so only changes go out
+        let mut told: Vec<Option<Vec<u8>>> = vec![None; clock.outputs.len()];
+
+        while !clock.closed.load(Ordering::SeqCst) {
+            // replaced by the watchdog while wedged
+            if clock.generation.load(Ordering::SeqCst) != generation {
+                return;
+            }
+            clock.beat(time.now());
+            if crash::crashed() {
+                conns.silence();
+                return;
+            }
+            let mut running = clock.running.load(Ordering::SeqCst);
+            for Timed { at, request } in clock.inbox.lock().unwrap().try_iter() {
+                match request {
+                    Request::Tempo(bpm) => {
+                        clock.exact.store(0, Ordering::SeqCst);
+                        clock.bpm.store(bpm, Ordering::SeqCst);
+                    }
+                    Request::Exact(bpm) => {
+                        clock.exact.store(bpm.to_bits(), Ordering::SeqCst);
+                        clock.bpm.store(bpm.round() as i32, Ordering::SeqCst);
+                    }
+                    Request::Glide(bpm, beats) if running && was_running && beats > 0 => {
+                        let from = match clock.tempo() {
+                            t if t > 0.0 => t,
+                            _ => clock.manual(),
+                        };
+                        clock.exact.store(0, Ordering::SeqCst);
+                        let base = clock.bpm.load(Ordering::SeqCst);
+                        ramp = Some(Ramp::glide(from, bpm, clock.position.load(Ordering::SeqCst), beats, base));
+                    }
+                    Request::Glide(bpm, _) => {
+                        clock.exact.store(0, Ordering::SeqCst);
+                        clock.bpm.store(bpm, Ordering::SeqCst);
+                        ramp = None;
+                    }
+                    Request::Start(from) if !running => {
+                        clock.position.store(from, Ordering::SeqCst);
+                        resuming = from > 0;
+                        running = true;
+                        clock.running.store(true, Ordering::SeqCst);
+                        // the downbeat goes out as asked, not wherever the idle ticks were
+                        next_tick = at.max(time.now());
+                        next_frame = None;
+                    }
+                    Request::Start(_) => {}
+                    Request::Stop => {
+                        running = false;
+                        clock.running.store(false, Ordering::SeqCst);
+                    }
+                    Request::Locate(tick) => clock.seek(tick),
+                }
+            }
+
+            // take what the connection thread opened, close disabled ports
+            for (index, mut conn) in opened.try_iter() {
+                handshake(conn.as_mut(), index, &clock.outputs[index], &time);
+                told[index] = None;
+                conns[index] = Some(conn);
+            }
+            for (index, output) in clock.outputs.iter().enumerate() {
+                if !output.enabled.load(Ordering::SeqCst) && conns[index].take().is_some() {
+                    session::record(&clock.name, Kind::Port, format!("Closed port {}", index + 1));
+                    output.connected.store(false, Ordering::SeqCst);
+                }
+            }
+            // somewhere for the ticks to go, a port or other instances following this one
+            let connected = conns.iter().any(Option::is_some) || clock.net.lock().unwrap().enabled;
+            for msg in clock.outbox.lock().unwrap().drain(..) {
+                send(&mut conns, &clock.outputs, &time, &msg);
+            }
+            midi_ticks(&mut conns, &clock, &time, &mut next_midi_tick, time.now());
+
+            let seek = clock.seek.swap(NO_SEEK, Ordering::SeqCst);
+            if seek != NO_SEEK {
+                let position = seek - seek % 6;
+                clock.position.store(position, Ordering::SeqCst);
+                if running && was_running {
+                    relocate(&mut conns, &clock.outputs, &time, position);
+                    clock.locate(position, time.now());
+                }
+            }
+
+            // stop doesn't have to wait for a tick
+            if was_running && !running {
+                session::record(&clock.name, Kind::Transport, format!("Stop at tick {}", clock.position.load(Ordering::SeqCst)));
+                let _ = publish.send(mqtt::Event::Transport(false));
+                clock.wake();
+                send(&mut conns, &clock.outputs, &time, &[0xFC]);
+                *clock.origin.lock().unwrap() = None;
+                dmx.dark(&clock.dmx.lock().unwrap());
+                was_running = false;
+                ramp = None;
+            }
+            // wall-clock timers, a start goes through the armed start so it lands on time
+            let now = time.system();
+            for timer in clock.timers.lock().unwrap().iter_mut() {
+                let Some(at) = timer.at else { continue };
+                let lead = if timer.action == schedule::Action::Start { ARM_HORIZON } else { Duration::ZERO };
+                if at > now + lead {
+                    continue;
+                }
+                match timer.action {
+                    schedule::Action::Start if !running => *clock.armed.lock().unwrap() = Some(at),
+                    schedule::Action::Start => {}
+                    schedule::Action::Stop => clock.running.store(false, Ordering::SeqCst),
+                    schedule::Action::Bpm(bpm) => {
+                        clock.move_bpm(bpm);
+                        ramp = None;
+                    }
+                }
+                let what = match timer.action {
+                    schedule::Action::Bpm(bpm) => format!("{} BPM", bpm),
+                    action => action.name().to_string(),
+                };
+                session::record(&clock.name, Kind::Transport, format!("Timer at {}: {}", timer.time, what));
+                timer.done(now);
+            }
+
+            if !running {
+                held = None;
+            } else if was_running && clock.hold.load(Ordering::SeqCst) {
+                if held.is_none() {
+                    let stop = clock.hold_stop.load(Ordering::SeqCst);
+                    if stop {
+                        send(&mut conns, &clock.outputs, &time, &[0xFC]);
+                    }
+                    session::record(&clock.name, Kind::Transport, format!("Hold at tick {}", clock.position.load(Ordering::SeqCst)));
+                    held = Some(stop);
+                    clock.wake();
+                }
+                time.sleep(Duration::from_millis(1));
+                continue;
+            } else if let Some(stopped) = held.take() {
+                // the next tick goes out now, the one that was due when the hold began
+                if stopped {
+                    send(&mut conns, &clock.outputs, &time, &[0xFB]);
+                }
+                session::record(&clock.name, Kind::Transport, "Released hold".to_string());
+                next_tick = time.now();
+                next_frame = None;
+                clock.locate(clock.position.load(Ordering::SeqCst), time.now());
+            }
+
+            // an armed start moves the next tick onto the set time, so that's where Start goes out
+            let mut armed = clock.armed.lock().unwrap();
+            if running {
+                *armed = None;
+            } else if let Some(at) = *armed {
+                let wait = at.duration_since(time.system()).unwrap_or_default();
+                if wait < ARM_HORIZON {
+                    next_tick = time.now() + wait;
+                    next_frame = None;
+                    running = true;
+                    clock.running.store(true, Ordering::SeqCst);
+                    session::record(&clock.name, Kind::Transport, "Scheduled start".to_string());
+                    *armed = None;
+                }
+            }
+            drop(armed);
+            if running && !was_running && !resuming {
+                clock.position.store(0, Ordering::SeqCst);
+            }
+            // a plain start waits out the longest pre-roll, gated devices get clock first to lock on
+            if !running {
+                prerolled = 0;
+            }
+            let preroll = clock.outputs.iter()
+                .filter(|o| o.enabled.load(Ordering::SeqCst))
+                .map(|o| o.preroll.load(Ordering::SeqCst) as u64 * 24)
+                .max()
+                .unwrap_or(0);
+            let prerolling = running && !was_running && !resuming && prerolled < preroll;
+
+            // cues fire once each time the transport passes their bar
+            let position = clock.position.load(Ordering::SeqCst);
+            if running && !prerolling && fired != Some(position) {
+                for cue in clock.cues.lock().unwrap().iter().filter(|c| c.tick() == position) {
+                    if let Some(m) = &cue.msc {
+                        send(&mut conns, &clock.outputs, &time, &m.sysex(&clock.msc.lock().unwrap()));
+                    }
+                    if cue.over == 0 {
+                        clock.move_bpm(cue.bpm);
+                        ramp = None;
+                    } else {
+                        let base = clock.bpm.load(Ordering::SeqCst);
+                        let from = match clock.tempo() {
+                            t if t > 0.0 => t,
+                            _ => base as f64,
+                        };
+                        ramp = Some(Ramp::new(from, cue, position, base));
+                    }
+                }
+                fired = Some(position);
+            }
+            if clock.slow_stop.swap(false, Ordering::SeqCst) && running && was_running {
+                let base = clock.bpm.load(Ordering::SeqCst);
+                let beats = clock.slow_stop_beats.load(Ordering::SeqCst);
+                session::record(&clock.name, Kind::Transport, format!("Slow stop over {} beats", beats));
+                ramp = Some(Ramp::ritardando(clock.tempo(), position, beats, base));
+            }
+            // touching the tempo by hand cancels a glide, finishing one commits its target
+            if let Some(r) = &ramp {
+                if clock.bpm.load(Ordering::SeqCst) != r.base {
+                    ramp = None;
+                } else if r.done(position) {
+                    // a slow stop leaves the set tempo as it was for the next Start, and
+                    // goes round again so Stop is sent in place of this tick
+                    if r.stop {
+                        clock.running.store(false, Ordering::SeqCst);
+                        ramp = None;
+                        continue;
+                    }
+                    clock.move_bpm(r.to);
+                    ramp = None;
+                }
+            }
+            let val = clock.bpm.load(Ordering::SeqCst);
+            let manual = clock.manual();
+            if manual != logged_bpm {
+                session::record(&clock.name, Kind::Tempo, format!("{} BPM", manual));
+                logged_bpm = manual;
+            }
+
+            // switching source only changes where timing comes from, the connection stays up
+            let source = Source::load(&clock.source);
+            let external = source != Source::Internal;
+            let interval = if matches!(source, Source::Ltc | Source::Network) {
+                // both say where the song is, not just how fast it goes
+                let lock = match source {
+                    // the set tempo at the timecode's speed, on a grid laid from the song's start
+                    Source::Ltc => chase
+                        .lock()
+                        .unwrap()
+                        .lock_point(time.now())
+                        .filter(|_| val > 0)
+                        .map(|(zero, speed)| (zero, 60.0 / (manual * 24.0) / speed)),
+                    _ => follower.lock().unwrap().lock_point(time.now()),
+                };
+                match lock {
+                    Some((zero, period)) if connected => {
+                        applied_offset = clock.offset.secs(period);
+                        let zero = reclock::shift(zero, applied_offset);
+                        let at = reclock::secs_between(zero, time.now()) / period;
+                        // slaves can only be placed on 16ths
+                        let target = (at / 6.0).ceil() as u64 * 6;
+                        if !running {
+                            // timecode rolling or the master starting starts the slaves where it says
+                            clock.position.store(target, Ordering::SeqCst);
+                            clock.running.store(true, Ordering::SeqCst);
+                            resuming = true;
+                            chasing = true;
+                            continue;
+                        }
+                        if position.abs_diff(target) > 24 {
+                            clock.seek(target);
+                        }
+                        next_tick = reclock::shift(zero, position as f64 * period);
+                        next_frame = None;
+                        Duration::from_secs_f64(period)
+                    }
+                    _ => {
+                        // timecode or the master stopped, so does the transport it started
+                        if chasing && running {
+                            clock.running.store(false, Ordering::SeqCst);
+                        }
+                        chasing = false;
+                        time.sleep(nap(&clock, &time, next_midi_tick, Duration::from_millis(10)));
+                        continue;
+                    }
+                }
+            } else if external {
+                let lock = pll.lock().unwrap().lock_point(time.now());
+                match lock {
+                    Some((anchor, period)) if connected => {
+                        // follow the smoothed grid instead of the raw incoming ticks
+                        applied_offset = clock.offset.secs(period);
+                        let anchor = reclock::shift(anchor, applied_offset);
+                        next_tick = reclock::snap(next_tick, anchor, period, time.now());
+                        next_frame = None;
+                        Duration::from_secs_f64(period)
+                    }
+                    _ => {
+                        // waiting for the master, lock can come at any moment
+                        time.sleep(nap(&clock, &time, next_midi_tick, Duration::from_millis(10)));
+                        continue;
+                    }
+                }
+            } else {
+                // automation takes over the tempo while the transport runs, then cue glides
+                let automation = clock.automation.lock().unwrap();
+                let bpm = match automation.tempo_at(position as f64 / 24.0) {
+                    Some(bpm) if automation.enabled && running && !ramp.as_ref().is_some_and(|r| r.stop) => bpm,
+                    _ => ramp.as_ref().map_or(manual, |r| r.tempo(position)),
+                };
+                drop(automation);
+
+                if bpm <= 0.0 || !connected {
+                    clock.tempo.store(0f64.to_bits(), Ordering::SeqCst);
+                    slewed = None;
+                    time.idle(&clock, nap(&clock, &time, next_midi_tick, Duration::from_millis(500)));
+                    continue;
+                }
+
+                // taps and typed tempos reach the slaves as short glides they can follow
+                let limit = clock.slew.load(Ordering::SeqCst) as f64;
+                let now = time.now();
+                let bpm = match slewed {
+                    Some((last, at)) if limit > 0.0 && running => {
+                        let step = limit * now.duration_since(at).as_secs_f64();
+                        last + (bpm - last).clamp(-step, step)
+                    }
+                    _ => bpm,
+                };
+                slewed = Some((bpm, now));
+
+                let interval_ms = 60000.0 / (bpm * 24.0);
+                Duration::from_secs_f64(interval_ms / 1000.0)
+            };
+            let tempo = 60.0 / (interval.as_secs_f64() * 24.0);
+            clock.tempo.store(tempo.to_bits(), Ordering::SeqCst);
+            for (index, ((conn, output), told)) in conns.iter_mut().zip(&clock.outputs).zip(told.iter_mut()).enumerate() {
+                let template = output.tempo_sysex.lock().unwrap();
+                let (Some(conn), Some(template)) = (conn, template.as_ref()) else { continue };
+                let msg = template.fill(tempo);
+                if told.as_ref() != Some(&msg) && !output.muted.load(Ordering::SeqCst) {
+                    let _ = conn.send(&msg);
+                    capture::record(time.now(), index, &msg);
+                    *told = Some(msg);
+                }
+            }
+            if (tempo * 100.0).round() as i64 != published {
+                published = (tempo * 100.0).round() as i64;
+                // glides and automation move the shown tempo between beats
+                if tempo.round() as i32 != shown {
+                    shown = tempo.round() as i32;
+                    clock.wake();
+                }
+                let _ = publish.send(mqtt::Event::Tempo(tempo));
+            }
+
+            // test mode: random walk the tempo, this one accumulates into drift
+            let amount = clock.humanize.wander();
+            let interval = if amount > 0.0 {
+                wander = (wander + rng.signed() * 0.01).clamp(-1.0, 1.0);
+                interval.mul_f64(1.0 + wander * amount)
+            } else {
+                interval
+            };
+            // test mode: a steady creep, unlike the wander it only goes one way
+            let rate = clock.humanize.drift();
+            if rate == 0.0 || clock.humanize.drift_reset.swap(false, Ordering::SeqCst) {
+                drifted = 0.0;
+            }
+            drift_at = match drift_at {
+                Some(at) if rate != 0.0 && running => {
+                    let now = time.now();
+                    drifted += rate * reclock::secs_between(at, now);
+                    Some(now)
+                }
+                _ => (rate != 0.0 && running).then(|| time.now()),
+            };
+            clock.humanize.drifted.store((drifted * 1_000_000.0).to_bits(), Ordering::SeqCst);
+            let interval = interval.div_f64((1.0 + drifted).max(0.01));
+
+            // shift the schedule by however much the offset changed. going earlier can't
+            // reach back past now, that would burst out the ticks in between
+            let offset = clock.offset.secs(interval.as_secs_f64());
+            if offset != applied_offset {
+                next_tick = reclock::shift(next_tick, offset - applied_offset).max(next_tick.min(time.now()));
+                next_frame = None;
+                applied_offset = offset;
+            }
+
+            // audio timing counts the schedule in frames, so it runs at the sound card's rate
+            let audio_rate = if clock.audio.load(Ordering::SeqCst) && !external { audio.rate() } else { None };
+            match audio_rate {
+                Some(_) => {
+                    let frame = *next_frame.get_or_insert_with(|| audio.frame_at(next_tick).unwrap_or(0.0));
+                    if let Some(t) = audio.instant_at(frame) {
+                        next_tick = t;
+                    }
+                }
+                None => next_frame = None,
+            }
+
+            // test mode: jitter moves single ticks, never the schedule
+            let jitter = clock.humanize.jitter();
+            let send_at = if jitter > 0.0 { reclock::shift(next_tick, rng.signed() * jitter) } else { next_tick };
+
+            // MIDI Tick runs at its own fixed rate, squeezed in between the clock ticks
+            midi_ticks(&mut conns, &clock, &time, &mut next_midi_tick, send_at);
+
+            // on audio timing the sound card's callback wakes the thread for the buffer
+            // holding the tick, leaving only the frames ahead of it to spin through
+            match next_frame.filter(|_| audio_rate.is_some()) {
+                Some(frame) if time.wait_frame(&audio, frame, reclock::shift(send_at, AUDIO_STALL)) => {
+                    time.wait_until(send_at, Sleep::Spin);
+                }
+                _ => time.wait_until(send_at, Sleep::load(&clock.sleep)),
+            }
+            clock.beat(time.now());
+            if crash::crashed() {
+                conns.silence();
+                return;
+            }
+            clock.stats.late(reclock::secs_between(send_at, time.now()));
+
+            // devices waiting on a handshake get it again before anything of the start
+            if running && !was_running && prerolled == 0 {
+                for (index, (conn, output)) in conns.iter_mut().zip(&clock.outputs).enumerate() {
+                    if let Some(conn) = conn {
+                        handshake(conn.as_mut(), index, output, &time);
+                    }
+                }
+            }
+            // start goes right before the tick that becomes the downbeat
+            if running && !was_running && resuming {
+                let position = clock.position.load(Ordering::SeqCst);
+                clock.position.store(position - position % 6, Ordering::SeqCst);
+                relocate(&mut conns, &clock.outputs, &time, position);
+                session::record(&clock.name, Kind::Transport, format!("Continued from tick {}", position));
+                clock.wake();
+                was_running = true;
+            }
+            resuming = false;
+            if prerolling {
+                prerolled += 1;
+            } else if running && !was_running {
+                send(&mut conns, &clock.outputs, &time, &[0xFA]);
+                *clock.origin.lock().unwrap() = Some(time.now());
+                session::record(&clock.name, Kind::Transport, "Start".to_string());
+                let _ = publish.send(mqtt::Event::Transport(true));
+                clock.wake();
+                was_running = true;
+            }
+            // after a cut wait for a 16th so the slaves can be relocated onto our position
+            let position = clock.position.load(Ordering::SeqCst);
+            if clock.cut.load(Ordering::SeqCst) || (was_cut && running && !position.is_multiple_of(6)) {
+                was_cut = true;
+            } else {
+                if was_cut && running {
+                    relocate(&mut conns, &clock.outputs, &time, position);
+                }
+                was_cut = false;
+                let live = was_running || prerolling;
+                let gated = |o: &Output| !o.gate.load(Ordering::SeqCst) || live;
+                send_if(&mut conns, &clock.outputs, &time, &[0xF8], |o| !midi_tick(o) && gated(o));
+                clock.stats.ticks.fetch_add(1, Ordering::SeqCst);
+                network.tick(&clock.net.lock().unwrap(), running && !prerolling, tempo, position);
+                // 48 and 96 ppqn devices get the extra clocks spread evenly inside the tick
+                let finest = clock.outputs.iter().filter(|o| o.enabled.load(Ordering::SeqCst)).map(ticks).max().unwrap_or(1);
+                for i in 1..finest {
+                    let at = send_at + interval * i / finest;
+                    midi_ticks(&mut conns, &clock, &time, &mut next_midi_tick, at);
+                    time.wait_until(at, Sleep::load(&clock.sleep));
+                    send_if(&mut conns, &clock.outputs, &time, &[0xF8], |o| {
+                        let k = ticks(o);
+                        k > 1 && i % (finest / k) == 0 && !midi_tick(o) && gated(o)
+                    });
+                }
+            }
+            if running && !prerolling {
+                dmx.tick(&clock.dmx.lock().unwrap(), position);
+                #[cfg(feature = "gpio")]
+                if clock.gpio.lock().unwrap().due(position) {
+                    let _ = pulse.send(());
+                }
+                if position.is_multiple_of(24) {
+                    let _ = flash.send(position.is_multiple_of(TICKS_PER_BAR));
+                    clock.wake();
+                    let _ = publish.send(mqtt::Event::Beat(position));
+                }
+            }
+            if running && !prerolling {
+                let position = clock.position.fetch_add(1, Ordering::SeqCst) + 1;
+                let automation = clock.automation.lock().unwrap();
+                if automation.active() && automation.looping && position >= automation.bars as u64 * TICKS_PER_BAR {
+                    clock.seek(0);
+                }
+                // the stop goes out before what would be the next bar's downbeat
+                let bars = clock.stop_after.load(Ordering::SeqCst) as u64;
+                if bars > 0 && position >= bars * TICKS_PER_BAR {
+                    session::record(&clock.name, Kind::Transport, format!("Stopped after {} bars", bars));
+                    clock.running.store(false, Ordering::SeqCst);
+                }
+            }
+
+            next_tick += interval;
+            if let (Some(frame), Some(rate)) = (next_frame.as_mut(), audio_rate) {
+                *frame += interval.as_secs_f64() * rate;
+            }
+        }
+    });
+    match handle {
+        Ok(handle) => *clock.thread.lock().unwrap() = Some(handle),
+        Err(e) => eprintln!("Failed to start clock thread: {}", e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::time::Simulated;
+
+    // a tick at 120 BPM
+    const TICK: f64 = 60.0 / (120.0 * 24.0);
+
+    // what the thread sent, with the simulated time in seconds it went out at
+    type Sent = Arc<Mutex<Vec<(f64, Vec<u8>)>>>;
+
+    struct Recorder {
+        time: Arc<Simulated>,
+        sent: Sent,
+    }
+
+    impl Sink for Recorder {
+        fn send(&mut self, msg: &[u8]) -> Result<(), midir::SendError> {
+            self.sent.lock().unwrap().push((self.time.elapsed().as_secs_f64(), msg.to_vec()));
+            Ok(())
+        }
+    }
+
+    // the port is open from the start
+    impl Ports for Receiver<(usize, Box<dyn Sink>)> {
+        fn open(self, _: Arc<Clock>, _: u64) -> Receiver<(usize, Box<dyn Sink>)> {
+            self
+        }
+    }
+
+    // a master sending MIDI clock at `period`, heard whenever the clock thread waits
+    struct Upstream {
+        time: Arc<Simulated>,
+        pll: Arc<Mutex<Pll>>,
+        period: f64,
+        next: Mutex<Instant>,
+    }
+
+    impl Time for Upstream {
+        fn now(&self) -> Instant {
+            self.time.now()
+        }
+
+        fn system(&self) -> SystemTime {
+            self.time.system()
+        }
+
+        fn wait_until(&self, deadline: Instant, sleep: Sleep) {
+            let mut next = self.next.lock().unwrap();
+            while *next <= deadline {
+                self.time.wait_until(*next, sleep);
+                self.pll.lock().unwrap().tick(*next);
+                *next = reclock::shift(*next, self.period);
+            }
+            self.time.wait_until(deadline, sleep);
+        }
+    }
+
+    // runs a clock on one port until `ticks` clocks have gone out after Start,
+    // returns their times in seconds
+    fn run(clock: Arc<Clock>, pll: Arc<Mutex<Pll>>, time: Arc<Simulated>, with: impl Time + 'static, ticks: usize) -> Vec<f64> {
+        let sent: Sent = Arc::new(Mutex::new(Vec::new()));
+        let (tx, rx) = mpsc::channel();
+        tx.send((0, Box::new(Recorder { time, sent: Arc::clone(&sent) }) as Box<dyn Sink>)).unwrap();
+        clock.outputs[0].enabled.store(true, Ordering::SeqCst);
+        clock.running.store(true, Ordering::SeqCst);
+        let chase = Arc::new(Mutex::new(Chase::new()));
+        let follower = Arc::new(Mutex::new(Follower::new()));
+        spawn(Arc::clone(&clock), pll, chase, follower, Arc::new(AudioClock::new()), rx, with);
+        let clocks = |sent: &[(f64, Vec<u8>)]| -> Vec<f64> {
+            let start = sent.iter().position(|(_, m)| m[..] == [0xFA]).map_or(sent.len(), |i| i + 1);
+            sent[start..].iter().filter(|(_, m)| m[..] == [0xF8]).map(|&(t, _)| t).collect()
+        };
+        let given_up = Instant::now() + Duration::from_secs(10);
+        while clocks(&sent.lock().unwrap()).len() < ticks {
+            assert!(Instant::now() < given_up, "clock thread stopped sending");
+            thread::sleep(Duration::from_millis(1));
+        }
+        clock.close();
+        let mut times = clocks(&sent.lock().unwrap());
+        times.truncate(ticks);
+        times
+    }
+
+    fn simulated() -> Arc<Simulated> {
+        Arc::new(Simulated::new(SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000)))
+    }
+
+    fn close(a: f64, b: f64) -> bool {
+        (a - b).abs() < 1e-6
+    }
+
+    #[test]
+    fn ramp() {
+        let clock = Arc::new(Clock::new(1, "Ramp".to_string()));
+        clock.bpm.store(120, Ordering::SeqCst);
+        // from the second bar up to 180 over one bar
+        clock.cues.lock().unwrap().push(Cue { bar: 2, bpm: 180, over: 1, msc: None });
+        let time = simulated();
+        let times = run(Arc::clone(&clock), Arc::new(Mutex::new(Pll::new())), Arc::clone(&time), Arc::clone(&time), 400);
+        for (position, gap) in times.windows(2).map(|w| w[1] - w[0]).enumerate() {
+            let bpm = match position as u64 {
+                p if p < TICKS_PER_BAR => 120.0,
+                p if p < TICKS_PER_BAR * 2 => 120.0 + 60.0 * (p - TICKS_PER_BAR) as f64 / TICKS_PER_BAR as f64,
+                _ => 180.0,
+            };
+            assert!(close(gap, 60.0 / (bpm * 24.0)), "tick {} after {} s, wanted {} BPM", position, gap, bpm);
+        }
+        assert_eq!(clock.bpm.load(Ordering::SeqCst), 180);
+    }
+
+    #[test]
+    fn drift_correction() {
+        // a master 500 ppm fast, the set tempo doesn't matter while following
+        let period = TICK / 1.0005;
+        let clock = Arc::new(Clock::new(1, "Follow".to_string()));
+        Source::ExternalMidi.store(&clock.source);
+        let time = simulated();
+        let pll = Arc::new(Mutex::new(Pll::new()));
+        let master = Upstream { time: Arc::clone(&time), pll: Arc::clone(&pll), period, next: Mutex::new(time.now()) };
+        let times = run(clock, pll, Arc::clone(&time), master, 24 * 40);
+        // Start goes out as soon as the lock comes, every tick after lands on one of the master's
+        let first = times[1];
+        for &t in &times[1..] {
+            let off = (t - first) / period;
+            assert!((off - off.round()).abs() * period < 1e-6, "tick at {} s is off the master's grid", t);
+        }
+        let mean = (times[times.len() - 1] - first) / (times.len() - 2) as f64;
+        assert!(close(mean, period), "ticks every {} s, master every {} s", mean, period);
+    }
+}
@@ -0,0 +1,129 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+const FILE: &str = "midiclock.cfg";
+// an empty file by this name next to the executable keeps everything there
+const PORTABLE: &str = "portable";
+// settings profiles, shareable between machines
+pub const PROFILE_EXT: &str = "midiclock";
+
+// plain `key = value` lines, keys may repeat for lists
+pub struct Config {
+    entries: Vec<(String, String)>,
+}
+
+impl Config {
+    pub fn new() -> Self {
+        Self { entries: Vec::new() }
+    }
+
+    pub fn load() -> Self {
+        Self::parse(&fs::read_to_string(path()).unwrap_or_default())
+    }
+
+    pub fn read(path: &Path) -> Result<Self, String> {
+        let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        Ok(Self::parse(&text))
+    }
+
+    fn parse(text: &str) -> Self {
+        let entries = text
+            .lines()
+            .filter(|l| !l.trim_start().starts_with('#'))
+            .filter_map(|l| l.split_once('='))
+            .map(|(k, v)| (k.trim().to_string(), v.trim().to_string()))
+            .collect();
+        Self { entries }
+    }
+
+    pub fn save(&self) {
+        if let Err(e) = self.write(&path()) {
+            eprintln!("{}", e);
+        }
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), String> {
+        if let Some(dir) = path.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        let text: String = self.entries.iter().map(|(k, v)| format!("{} = {}\n", k, v)).collect();
+        fs::write(path, text).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+    }
+
+    pub fn all(&self, key: &str) -> Vec<&str> {
+        self.entries.iter().filter(|(k, _)| k == key).map(|(_, v)| v.as_str()).collect()
+    }
+
+    pub fn first(&self, key: &str) -> Option<&str> {
+        self.all(key).first().copied()
+    }
+
+    pub fn add(&mut self, key: &str, value: String) {
+        self.entries.push((key.to_string(), value));
+    }
+
+    pub fn set_all(&mut self, key: &str, values: Vec<String>) {
+        self.remove(key);
+        self.entries.extend(values.into_iter().map(|v| (key.to_string(), v)));
+    }
+
+    pub fn remove(&mut self, key: &str) {
+        self.entries.retain(|(k, _)| k != key);
+    }
+}
+
+// the executable's directory when running off a stick, from the marker file or
+// `--portable` for a single run
+pub fn portable() -> Option<&'static Path> {
+    static DIR: OnceLock<Option<PathBuf>> = OnceLock::new();
+    DIR.get_or_init(|| {
+        let dir = std::env::current_exe().ok()?.parent()?.to_path_buf();
+        let on = dir.join(PORTABLE).is_file() || std::env::args().any(|a| a == "--portable");
+        on.then_some(dir)
+    })
+    .as_deref()
+}
+
+// where exports, captures and reports go: beside the executable when portable,
+// the working directory otherwise
+pub fn output(name: &str) -> PathBuf {
+    match portable() {
+        Some(dir) => dir.join(name),
+        None => PathBuf::from(name),
+    }
+}
+
+// %APPDATA% on windows, ~/.config elsewhere, the working directory as a last resort
+fn path() -> PathBuf {
+    if let Some(dir) = portable() {
+        return dir.join(FILE);
+    }
+    let dir = std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .or_else(|| std::env::var_os("XDG_CONFIG_HOME").map(PathBuf::from))
+        .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")));
+    match dir {
+        Some(dir) => dir.join("midiclock").join(FILE),
+        None => PathBuf::from(FILE),
+    }
+}
+
+// profiles live in their own directory next to the config, a file each
+pub fn profile_path(name: &str) -> PathBuf {
+    path().with_file_name("profiles").join(format!("{}.{}", name, PROFILE_EXT))
+}
+
+pub fn profiles() -> Vec<String> {
+    let Ok(dir) = fs::read_dir(path().with_file_name("profiles")) else {
+        return Vec::new();
+    };
+    let mut names: Vec<String> = dir
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|e| e == PROFILE_EXT))
+        .filter_map(|p| Some(p.file_stem()?.to_string_lossy().into_owned()))
+        .collect();
+    names.sort();
+    names
+}
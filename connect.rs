@@ -0,0 +1,90 @@
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+use midir::{MidiOutput, MidiOutputConnection, MidiOutputPort, SendError};
+use crate::clock::Clock;
+use crate::session::{self, Kind};
+
+// how often enabled ports are checked, and how long a failed one waits
+const CHECK: Duration = Duration::from_millis(100);
+const RETRY: Duration = Duration::from_secs(1);
+
+// where a clock thread's messages go, an open port normally
+pub trait Sink: Send {
+    fn send(&mut self, msg: &[u8]) -> Result<(), SendError>;
+}
+
+impl Sink for MidiOutputConnection {
+    fn send(&mut self, msg: &[u8]) -> Result<(), SendError> {
+        MidiOutputConnection::send(self, msg)
+    }
+}
+
+// what a clock thread gets its sinks from, each as it becomes ready
+pub trait Ports: Send {
+    fn open(self, clock: Arc<Clock>, generation: u64) -> Receiver<(usize, Box<dyn Sink>)>;
+}
+
+impl Ports for Vec<MidiOutputPort> {
+    fn open(self, clock: Arc<Clock>, generation: u64) -> Receiver<(usize, Box<dyn Sink>)> {
+        spawn(clock, self, generation)
+    }
+}
+
+// opens enabled ports off the clock thread, a driver that takes its time to connect
+// or fail never holds up ticks on the ports that are already open. connections are
+// handed over on the channel, the clock thread closes them itself when a port is
+// disabled and clears `connected` so a re-enable opens it again
+fn spawn(clock: Arc<Clock>, outports: Vec<MidiOutputPort>, generation: u64) -> Receiver<(usize, Box<dyn Sink>)> {
+    let (tx, rx) = mpsc::channel();
+    for output in &clock.outputs {
+        output.connected.store(false, Ordering::SeqCst);
+    }
+    let spawned = thread::Builder::new().name("connect".to_string()).spawn(move || {
+        let mut retry: Vec<Option<Instant>> = vec![None; outports.len()];
+        // replaced along with its clock thread by the watchdog
+        while !clock.closed() && clock.generation() == generation {
+            for (index, output) in clock.outputs.iter().enumerate() {
+                if !output.enabled.load(Ordering::SeqCst) {
+                    retry[index] = None;
+                    continue;
+                }
+                if output.connected.load(Ordering::SeqCst) || retry[index].is_some_and(|t| Instant::now() < t) {
+                    continue;
+                }
+                match MidiOutput::new("Rust Midi Output Thread").unwrap().connect(&outports[index], "midir-selected") {
+                    Ok(c) => {
+                        let verb = if retry[index].is_some() {
+                            clock.stats.reconnects.fetch_add(1, Ordering::SeqCst);
+                            "Reconnected"
+                        } else {
+                            "Connected"
+                        };
+                        session::record(&clock.name, Kind::Port, format!("{} port {}", verb, index + 1));
+                        retry[index] = None;
+                        output.connected.store(true, Ordering::SeqCst);
+                        if tx.send((index, Box::new(c) as Box<dyn Sink>)).is_err() {
+                            return;
+                        }
+                        clock.poke();
+                    }
+                    Err(e) => {
+                        eprintln!("Failed to connect to port {}: {}", index + 1, e);
+                        // only the first failure, it retries every second
+                        if retry[index].is_none() {
+                            session::record(&clock.name, Kind::Error, format!("Failed to connect to port {}: {}", index + 1, e));
+                        }
+                        retry[index] = Some(Instant::now() + RETRY);
+                    }
+                }
+            }
+            thread::sleep(CHECK);
+        }
+    });
+    if let Err(e) = spawned {
+        eprintln!("Failed to start connection thread: {}", e);
+    }
+    rx
+}
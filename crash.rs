@@ -0,0 +1,118 @@
+use std::cell::Cell;
+use std::ops::{Deref, DerefMut};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::connect::Sink;
+
+// how long a panic waits for the clock threads to stop their slaves
+const GRACE: Duration = Duration::from_millis(300);
+
+static CRASHED: AtomicBool = AtomicBool::new(false);
+// clock threads alive, and how many of them have stopped their slaves since the panic
+static LIVE: AtomicUsize = AtomicUsize::new(0);
+static STOPPED: AtomicUsize = AtomicUsize::new(0);
+
+type Ports = Vec<Option<Box<dyn Sink>>>;
+
+thread_local! {
+    // this clock thread's connections, for the panic hook, and whether they've had Stop
+    static OWN: Cell<*mut Ports> = const { Cell::new(std::ptr::null_mut()) };
+    static SILENCED: Cell<bool> = const { Cell::new(false) };
+}
+
+// a crash must not leave the drum machine running, so every clock thread
+// sends Stop and All Notes Off before the process goes. the release build aborts
+// on panic, nothing unwinds, so the hook is the only chance to do it
+pub fn install() {
+    let previous = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        previous(info);
+        let name = thread::current().name().map(str::to_string);
+        if name.as_deref() == Some("clock") {
+            silence_own();
+        }
+        // unwinding, only losing the gui ends the process: a clock thread gets
+        // restarted by the watchdog and helper threads just go. aborting, any
+        // panic takes everything down, so every clock has to stop its slaves now
+        if !cfg!(panic = "abort") && name.as_deref() != Some("main") {
+            return;
+        }
+        CRASHED.store(true, Ordering::SeqCst);
+        let deadline = Instant::now() + GRACE;
+        while STOPPED.load(Ordering::SeqCst) < LIVE.load(Ordering::SeqCst) && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+    }));
+}
+
+pub fn crashed() -> bool {
+    CRASHED.load(Ordering::SeqCst)
+}
+
+// from the hook, on the panicking clock thread itself
+fn silence_own() {
+    let own = OWN.with(Cell::get);
+    if own.is_null() {
+        return;
+    }
+    // SAFETY: set by this thread's Connections, which clears it when dropped, so it
+    // points at the live boxed Vec. the panicking code never touches it again
+    silence(unsafe { &mut *own });
+}
+
+// muted ports too, their slaves may have been started before the mute
+fn silence(ports: &mut Ports) {
+    if SILENCED.with(|s| s.replace(true)) {
+        return;
+    }
+    for conn in ports.iter_mut().flatten() {
+        let _ = conn.send(&[0xFC]);
+        for channel in 0..16 {
+            let _ = conn.send(&[0xB0 | channel, 123, 0]);
+        }
+    }
+    STOPPED.fetch_add(1, Ordering::SeqCst);
+}
+
+// a clock thread's output connections, silenced if the thread itself panics
+// or when it sees another one did. boxed so the hook can find them
+pub struct Connections(Box<Ports>);
+
+impl Connections {
+    pub fn new(ports: usize) -> Self {
+        LIVE.fetch_add(1, Ordering::SeqCst);
+        let mut conns = Self(Box::new((0..ports).map(|_| None).collect()));
+        OWN.with(|own| own.set(&mut *conns.0));
+        SILENCED.with(|s| s.set(false));
+        conns
+    }
+
+    pub fn silence(&mut self) {
+        silence(&mut self.0);
+    }
+}
+
+impl Deref for Connections {
+    type Target = Ports;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl DerefMut for Connections {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.0
+    }
+}
+
+impl Drop for Connections {
+    fn drop(&mut self) {
+        if thread::panicking() {
+            self.silence();
+        }
+        OWN.with(|own| own.set(std::ptr::null_mut()));
+        LIVE.fetch_sub(1, Ordering::SeqCst);
+    }
+}
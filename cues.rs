@@ -0,0 +1,57 @@
+use crate::clock::TICKS_PER_BAR;
+use crate::msc;
+
+// "at bar 33 go to 140 BPM", optionally gliding there over a few bars
+pub struct Cue {
+    // 1 based, like the bar counter everyone reads
+    pub bar: u32,
+    pub bpm: i32,
+    // bars to glide over, 0 jumps straight there
+    pub over: u32,
+    // show control sent along with it
+    pub msc: Option<msc::Message>,
+}
+
+impl Cue {
+    pub fn tick(&self) -> u64 {
+        (self.bar.max(1) - 1) as u64 * TICKS_PER_BAR
+    }
+}
+
+// a glide started by a cue or a slow stop, positions are ticks since Start
+pub struct Ramp {
+    from: f64,
+    pub to: i32,
+    start: u64,
+    len: u64,
+    // the manual tempo when the glide began, touching it cancels the glide
+    pub base: i32,
+    // ends in Stop instead of committing `to`
+    pub stop: bool,
+}
+
+impl Ramp {
+    pub fn new(from: f64, cue: &Cue, start: u64, base: i32) -> Self {
+        Self { from, to: cue.bpm, start, len: cue.over as u64 * TICKS_PER_BAR, base, stop: false }
+    }
+
+    // down to half speed over `beats`, then Stop
+    pub fn ritardando(from: f64, start: u64, beats: u32, base: i32) -> Self {
+        let len = beats.max(1) as u64 * 24;
+        Self { from, to: (from / 2.0).round() as i32, start, len, base, stop: true }
+    }
+
+    // over `beats` to `to`, for switching between tempo slots
+    pub fn glide(from: f64, to: i32, start: u64, beats: u32, base: i32) -> Self {
+        Self { from, to, start, len: beats.max(1) as u64 * 24, base, stop: false }
+    }
+
+    pub fn tempo(&self, position: u64) -> f64 {
+        let t = (position.saturating_sub(self.start) as f64 / self.len as f64).min(1.0);
+        self.from + (self.to as f64 - self.from) * t
+    }
+
+    pub fn done(&self, position: u64) -> bool {
+        position >= self.start + self.len
+    }
+}
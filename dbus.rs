@@ -0,0 +1,87 @@
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use zbus::blocking::{Connection, connection};
+use zbus::fdo;
+use crate::clock::{Clock, Request};
+
+pub const NAME: &str = "org.midiclock.Midiclock";
+const PATH: &str = "/org/midiclock/Midiclock";
+
+// scripting on the session bus, e.g.
+// busctl --user call org.midiclock.Midiclock /org/midiclock/Midiclock org.midiclock.Clock1 SetTempo ui 0 128
+// tabs and ports are counted from 0 in the order Tabs and Ports list them
+struct Control {
+    clocks: Arc<Mutex<Vec<Arc<Clock>>>>,
+    ports: Vec<String>,
+}
+
+impl Control {
+    fn clock(&self, tab: u32) -> fdo::Result<Arc<Clock>> {
+        let clocks = self.clocks.lock().unwrap();
+        clocks.get(tab as usize).cloned().ok_or(fdo::Error::InvalidArgs(format!("No tab {}", tab)))
+    }
+}
+
+#[zbus::interface(name = "org.midiclock.Clock1")]
+impl Control {
+    fn tabs(&self) -> Vec<String> {
+        self.clocks.lock().unwrap().iter().map(|c| c.name.clone()).collect()
+    }
+
+    fn tempo(&self, tab: u32) -> fdo::Result<f64> {
+        Ok(self.clock(tab)?.tempo())
+    }
+
+    fn set_tempo(&self, tab: u32, bpm: i32) -> fdo::Result<()> {
+        if !(1..=999).contains(&bpm) {
+            return Err(fdo::Error::InvalidArgs(format!("{} BPM is out of range", bpm)));
+        }
+        self.clock(tab)?.request(Request::Tempo(bpm));
+        Ok(())
+    }
+
+    fn running(&self, tab: u32) -> fdo::Result<bool> {
+        Ok(self.clock(tab)?.running.load(Ordering::SeqCst))
+    }
+
+    fn start(&self, tab: u32) -> fdo::Result<()> {
+        self.clock(tab)?.request(Request::Start(0));
+        Ok(())
+    }
+
+    fn stop(&self, tab: u32) -> fdo::Result<()> {
+        self.clock(tab)?.request(Request::Stop);
+        Ok(())
+    }
+
+    // output ports with their numbers, the first one is never used
+    fn ports(&self) -> Vec<(u32, String)> {
+        self.ports.iter().enumerate().skip(1).map(|(i, name)| (i as u32, name.clone())).collect()
+    }
+
+    fn enabled_ports(&self, tab: u32) -> fdo::Result<Vec<u32>> {
+        let clock = self.clock(tab)?;
+        Ok((1..clock.outputs.len()).filter(|&i| clock.outputs[i].enabled.load(Ordering::SeqCst)).map(|i| i as u32).collect())
+    }
+
+    fn set_port(&self, tab: u32, port: u32, enabled: bool) -> fdo::Result<()> {
+        let clock = self.clock(tab)?;
+        match clock.outputs.get(port as usize) {
+            Some(output) if port > 0 => {
+                output.enabled.store(enabled, Ordering::SeqCst);
+                clock.poke();
+                Ok(())
+            }
+            _ => Err(fdo::Error::InvalidArgs(format!("No port {}", port))),
+        }
+    }
+}
+
+// served for as long as the connection is kept
+pub fn serve(clocks: Arc<Mutex<Vec<Arc<Clock>>>>, ports: Vec<String>) -> Result<Connection, String> {
+    connection::Builder::session()
+        .and_then(|b| b.name(NAME))
+        .and_then(|b| b.serve_at(PATH, Control { clocks, ports }))
+        .and_then(|b| b.build())
+        .map_err(|e| format!("Failed to register {} on D-Bus: {}", NAME, e))
+}
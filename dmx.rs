@@ -0,0 +1,167 @@
+use std::net::{Ipv4Addr, SocketAddrV4, UdpSocket};
+use std::time::SystemTime;
+use crate::clock::TICKS_PER_BAR;
+
+const ARTNET_PORT: u16 = 6454;
+const SACN_PORT: u16 = 5568;
+// ticks a pulse stays at full, 1/8 of a beat
+const PULSE_TICKS: u64 = 3;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Protocol {
+    Off,
+    ArtNet,
+    Sacn,
+}
+
+impl Protocol {
+    pub const ALL: [Protocol; 3] = [Protocol::Off, Protocol::ArtNet, Protocol::Sacn];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Protocol::Off => "Off",
+            Protocol::ArtNet => "Art-Net",
+            Protocol::Sacn => "sACN",
+        }
+    }
+
+    // Art-Net's 15 bit port address, sACN's universes start at 1
+    pub fn universes(self) -> (u16, u16) {
+        match self {
+            Protocol::Sacn => (1, 63999),
+            _ => (0, 32767),
+        }
+    }
+}
+
+// beat and bar pulses as DMX channel values, for rigs that chase the clock
+pub struct Dmx {
+    pub protocol: Protocol,
+    // empty broadcasts (Art-Net) or uses the universe's multicast group (sACN)
+    pub target: String,
+    pub universe: u16,
+    // 1 based, 0 leaves it out
+    pub beat_channel: u16,
+    pub bar_channel: u16,
+}
+
+impl Dmx {
+    pub fn new() -> Self {
+        Self { protocol: Protocol::Off, target: String::new(), universe: 1, beat_channel: 1, bar_channel: 2 }
+    }
+
+    fn address(&self) -> Option<SocketAddrV4> {
+        let target = self.target.trim().parse::<Ipv4Addr>().ok();
+        match self.protocol {
+            Protocol::Off => None,
+            Protocol::ArtNet => Some(SocketAddrV4::new(target.unwrap_or(Ipv4Addr::BROADCAST), ARTNET_PORT)),
+            Protocol::Sacn => {
+                let [hi, lo] = self.universe.to_be_bytes();
+                Some(SocketAddrV4::new(target.unwrap_or(Ipv4Addr::new(239, 255, hi, lo)), SACN_PORT))
+            }
+        }
+    }
+}
+
+// lives on the clock thread
+pub struct Sender {
+    socket: Option<UdpSocket>,
+    sequence: u8,
+    cid: [u8; 16],
+    lit: bool,
+}
+
+impl Sender {
+    pub fn new(now: SystemTime) -> Self {
+        // sACN wants a fixed id per source, this one is per run
+        let mut rng = crate::testmode::Rng::new(now);
+        let cid = std::array::from_fn(|_| (rng.signed() * 127.0 + 128.0) as u8);
+        Self { socket: None, sequence: 0, cid, lit: false }
+    }
+
+    // call with the position of every tick sent while running
+    pub fn tick(&mut self, dmx: &Dmx, position: u64) {
+        if position.is_multiple_of(24) {
+            let bar = position.is_multiple_of(TICKS_PER_BAR);
+            self.send(dmx, true, bar);
+            self.lit = true;
+        } else if self.lit && position % 24 >= PULSE_TICKS {
+            self.dark(dmx);
+        }
+    }
+
+    pub fn dark(&mut self, dmx: &Dmx) {
+        if self.lit {
+            self.send(dmx, false, false);
+            self.lit = false;
+        }
+    }
+
+    fn send(&mut self, dmx: &Dmx, beat: bool, bar: bool) {
+        let Some(address) = dmx.address() else {
+            return;
+        };
+        let mut data = vec![0u8; dmx.beat_channel.max(dmx.bar_channel).clamp(2, 512).next_multiple_of(2) as usize];
+        for (channel, on) in [(dmx.beat_channel, beat), (dmx.bar_channel, bar)] {
+            if (1..=512).contains(&channel) && on {
+                data[channel as usize - 1] = 255;
+            }
+        }
+        self.sequence = self.sequence.wrapping_add(1).max(1);
+        let packet = match dmx.protocol {
+            Protocol::ArtNet => artnet(dmx.universe, self.sequence, &data),
+            _ => sacn(dmx.universe, self.sequence, &self.cid, &data),
+        };
+        if self.socket.is_none() {
+            self.socket = UdpSocket::bind("0.0.0.0:0").ok();
+            if let Some(socket) = &self.socket {
+                let _ = socket.set_broadcast(true);
+            }
+        }
+        if let Some(socket) = &self.socket {
+            let _ = socket.send_to(&packet, address);
+        }
+    }
+}
+
+// ArtDmx, universe is the 15 bit port address
+fn artnet(universe: u16, sequence: u8, data: &[u8]) -> Vec<u8> {
+    let mut p = b"Art-Net\0".to_vec();
+    p.extend([0x00, 0x50]); // OpDmx, little endian
+    p.extend([0, 14]); // protocol version
+    p.extend([sequence, 0]);
+    p.extend([(universe & 0xFF) as u8, ((universe >> 8) & 0x7F) as u8]);
+    p.extend((data.len() as u16).to_be_bytes());
+    p.extend(data);
+    p
+}
+
+// E1.31 data packet: root, framing and DMP layers
+fn sacn(universe: u16, sequence: u8, cid: &[u8; 16], data: &[u8]) -> Vec<u8> {
+    let len = 126 + data.len();
+    let flags = |n: usize| (0x7000 | n as u16).to_be_bytes();
+    let mut p = Vec::with_capacity(len);
+    p.extend([0x00, 0x10, 0x00, 0x00]);
+    p.extend(b"ASC-E1.17\0\0\0");
+    p.extend(flags(len - 16));
+    p.extend([0, 0, 0, 4]);
+    p.extend(cid);
+
+    p.extend(flags(len - 38));
+    p.extend([0, 0, 0, 2]);
+    let mut name = [0u8; 64];
+    name[..9].copy_from_slice(b"midiclock");
+    p.extend(name);
+    p.push(100); // priority
+    p.extend([0, 0]); // no sync address
+    p.push(sequence);
+    p.push(0); // options
+    p.extend(universe.to_be_bytes());
+
+    p.extend(flags(len - 115));
+    p.extend([0x02, 0xA1, 0x00, 0x00, 0x00, 0x01]);
+    p.extend((data.len() as u16 + 1).to_be_bytes());
+    p.push(0); // start code
+    p.extend(data);
+    p
+}
@@ -0,0 +1,98 @@
+use std::sync::Arc;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::thread;
+use std::time::Duration;
+use rppal::gpio::{Gpio as Pins, OutputPin};
+use crate::clock::{Clock, TICKS_PER_BAR};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Rate {
+    Off,
+    Tick,
+    Sixteenth,
+    Eighth,
+    Beat,
+    Bar,
+}
+
+impl Rate {
+    pub const ALL: [Rate; 6] = [Rate::Off, Rate::Tick, Rate::Sixteenth, Rate::Eighth, Rate::Beat, Rate::Bar];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rate::Off => "Off",
+            Rate::Tick => "24 ppqn",
+            Rate::Sixteenth => "16ths",
+            Rate::Eighth => "8ths",
+            Rate::Beat => "Beats",
+            Rate::Bar => "Bars",
+        }
+    }
+
+    fn ticks(self) -> Option<u64> {
+        match self {
+            Rate::Off => None,
+            Rate::Tick => Some(1),
+            Rate::Sixteenth => Some(6),
+            Rate::Eighth => Some(12),
+            Rate::Beat => Some(24),
+            Rate::Bar => Some(TICKS_PER_BAR),
+        }
+    }
+}
+
+// pulses on a Raspberry Pi pin for sync inputs that don't speak MIDI
+pub struct Gpio {
+    pub rate: Rate,
+    // BCM numbering
+    pub pin: u8,
+    pub width_ms: u32,
+    // why the pin couldn't be opened
+    pub error: Option<String>,
+}
+
+impl Gpio {
+    pub fn new() -> Self {
+        Self { rate: Rate::Off, pin: 17, width_ms: 5, error: None }
+    }
+
+    // whether the tick at this position gets a pulse
+    pub fn due(&self, position: u64) -> bool {
+        self.rate.ticks().is_some_and(|t| position.is_multiple_of(t))
+    }
+}
+
+// the pin is driven off the clock thread, a pulse's width never holds up a tick
+pub fn spawn(clock: Arc<Clock>) -> Sender<()> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || pulser(clock, rx));
+    tx
+}
+
+fn pulser(clock: Arc<Clock>, rx: Receiver<()>) {
+    let mut open: Option<(u8, OutputPin)> = None;
+    while rx.recv().is_ok() {
+        let (pin, width) = {
+            let gpio = clock.gpio.lock().unwrap();
+            (gpio.pin, Duration::from_millis(gpio.width_ms as u64))
+        };
+        if open.as_ref().is_none_or(|(p, _)| *p != pin) {
+            open = match Pins::new().and_then(|pins| pins.get(pin)) {
+                Ok(p) => {
+                    clock.gpio.lock().unwrap().error = None;
+                    Some((pin, p.into_output_low()))
+                }
+                Err(e) => {
+                    clock.gpio.lock().unwrap().error = Some(format!("Failed to open GPIO {}: {}", pin, e));
+                    None
+                }
+            };
+        }
+        let Some((_, output)) = open.as_mut() else { continue };
+        output.set_high();
+        thread::sleep(width);
+        output.set_low();
+        // pulses that came in meanwhile are dropped, the pin can't go any faster
+        while rx.try_recv().is_ok() {}
+    }
+}
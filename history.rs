@@ -0,0 +1,136 @@
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use crate::clock::Clock;
+
+// changes closer together than this are one step, a drag or a burst of arrow keys
+const SETTLE: Duration = Duration::from_millis(500);
+const DEPTH: usize = 100;
+
+// undo by snapshots, the gui reports what things look like every frame and
+// differences become steps
+pub struct History<T> {
+    undo: Vec<T>,
+    redo: Vec<T>,
+    current: Option<T>,
+    changed: Option<Instant>,
+}
+
+impl<T: Clone + PartialEq> History<T> {
+    pub fn new() -> Self {
+        Self { undo: Vec::new(), redo: Vec::new(), current: None, changed: None }
+    }
+
+    pub fn observe(&mut self, state: T, now: Instant) {
+        let Some(current) = &self.current else {
+            self.current = Some(state);
+            return;
+        };
+        if *current == state {
+            return;
+        }
+        // the start of a new step keeps the settled state to go back to
+        if self.changed.is_none_or(|t| now.duration_since(t) > SETTLE) {
+            self.undo.push(current.clone());
+            if self.undo.len() > DEPTH {
+                self.undo.remove(0);
+            }
+            self.redo.clear();
+        }
+        self.current = Some(state);
+        self.changed = Some(now);
+    }
+
+    // takes `state` as it is now without making it a step, for changes that
+    // weren't the user's
+    pub fn rebase(&mut self, state: T) {
+        self.current = Some(state);
+    }
+
+    // returns the state to put back
+    pub fn undo(&mut self) -> Option<T> {
+        let state = self.undo.pop()?;
+        self.redo.extend(self.current.replace(state.clone()));
+        self.changed = None;
+        Some(state)
+    }
+
+    pub fn redo(&mut self) -> Option<T> {
+        let state = self.redo.pop()?;
+        self.undo.extend(self.current.replace(state.clone()));
+        self.changed = None;
+        Some(state)
+    }
+}
+
+// the settings of one clock, the transport isn't a setting
+#[derive(Clone, PartialEq)]
+pub struct ClockState {
+    bpm: i32,
+    // enabled, muted, MIDI Tick
+    outputs: Vec<(bool, bool, bool)>,
+    source: usize,
+    offset: (i32, bool),
+    sleep: usize,
+    audio: bool,
+    // jitter, wander and drift
+    humanize: (u32, u32, i32),
+    slew: u32,
+    slow_stop_beats: u32,
+    hold_stop: bool,
+    stop_after: u32,
+}
+
+impl ClockState {
+    pub fn capture(clock: &Clock) -> Self {
+        Self {
+            bpm: clock.bpm.load(Ordering::SeqCst),
+            outputs: clock
+                .outputs
+                .iter()
+                .map(|o| {
+                    (
+                        o.enabled.load(Ordering::SeqCst),
+                        o.muted.load(Ordering::SeqCst),
+                        o.midi_tick.load(Ordering::SeqCst),
+                    )
+                })
+                .collect(),
+            source: clock.source.load(Ordering::SeqCst),
+            offset: (clock.offset.value.load(Ordering::SeqCst), clock.offset.ticks.load(Ordering::SeqCst)),
+            sleep: clock.sleep.load(Ordering::SeqCst),
+            audio: clock.audio.load(Ordering::SeqCst),
+            humanize: (
+                clock.humanize.jitter_us.load(Ordering::SeqCst),
+                clock.humanize.wander_ppm.load(Ordering::SeqCst),
+                clock.humanize.drift_ppm.load(Ordering::SeqCst),
+            ),
+            slew: clock.slew.load(Ordering::SeqCst),
+            slow_stop_beats: clock.slow_stop_beats.load(Ordering::SeqCst),
+            hold_stop: clock.hold_stop.load(Ordering::SeqCst),
+            stop_after: clock.stop_after.load(Ordering::SeqCst),
+        }
+    }
+
+    pub fn apply(&self, clock: &Clock) {
+        // straight in rather than by request, the snapshot taken later this frame has to see it
+        clock.bpm.store(self.bpm, Ordering::SeqCst);
+        for (output, &(enabled, muted, midi_tick)) in clock.outputs.iter().zip(&self.outputs) {
+            output.enabled.store(enabled, Ordering::SeqCst);
+            output.muted.store(muted, Ordering::SeqCst);
+            output.midi_tick.store(midi_tick, Ordering::SeqCst);
+        }
+        clock.source.store(self.source, Ordering::SeqCst);
+        clock.offset.value.store(self.offset.0, Ordering::SeqCst);
+        clock.offset.ticks.store(self.offset.1, Ordering::SeqCst);
+        clock.sleep.store(self.sleep, Ordering::SeqCst);
+        clock.audio.store(self.audio, Ordering::SeqCst);
+        clock.humanize.jitter_us.store(self.humanize.0, Ordering::SeqCst);
+        clock.humanize.wander_ppm.store(self.humanize.1, Ordering::SeqCst);
+        clock.humanize.drift_ppm.store(self.humanize.2, Ordering::SeqCst);
+        clock.slew.store(self.slew, Ordering::SeqCst);
+        clock.slow_stop_beats.store(self.slow_stop_beats, Ordering::SeqCst);
+        clock.hold_stop.store(self.hold_stop, Ordering::SeqCst);
+        clock.stop_after.store(self.stop_after, Ordering::SeqCst);
+        clock.poke();
+    }
+}
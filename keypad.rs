@@ -0,0 +1,62 @@
+use eframe::egui;
+
+const KEYS: [[&str; 3]; 4] = [["7", "8", "9"], ["4", "5", "6"], ["1", "2", "3"], ["C", "0", "<"]];
+
+// finger sized digits for typing a tempo on a touchscreen
+pub struct Keypad {
+    pub open: bool,
+    digits: String,
+}
+
+impl Keypad {
+    pub fn new() -> Self {
+        Self { open: false, digits: String::new() }
+    }
+
+    pub fn show(&mut self) {
+        self.open = true;
+        self.digits.clear();
+    }
+
+    // the tempo once OK is pressed on one inside `range`
+    pub fn ui(&mut self, ctx: &egui::Context, range: (i32, i32)) -> Option<i32> {
+        let bpm = self.digits.parse::<i32>().ok().filter(|b| (range.0..=range.1).contains(b));
+        let mut set = None;
+        let mut cancel = false;
+        let modal = egui::Modal::new(egui::Id::new("keypad")).show(ctx, |ui| {
+            let text = if self.digits.is_empty() { "--" } else { self.digits.as_str() };
+            ui.vertical_centered(|ui| {
+                ui.label(egui::RichText::new(text).size(40.0));
+                ui.label(format!("{} to {} BPM", range.0, range.1));
+            });
+            let key = |text: &str| egui::Button::new(egui::RichText::new(text).size(28.0));
+            egui::Grid::new("keypad_keys").show(ui, |ui| {
+                for row in KEYS {
+                    for k in row {
+                        if ui.add_sized([70.0, 60.0], key(k)).clicked() {
+                            match k {
+                                "C" => self.digits.clear(),
+                                "<" => {
+                                    self.digits.pop();
+                                }
+                                digit if self.digits.len() < 3 => self.digits.push_str(digit),
+                                _ => {}
+                            }
+                        }
+                    }
+                    ui.end_row();
+                }
+                cancel = ui.add_sized([70.0, 60.0], key("x")).clicked();
+                ui.label("");
+                if ui.add_enabled(bpm.is_some(), egui::Button::new(egui::RichText::new("OK").size(28.0)).min_size(egui::vec2(70.0, 60.0))).clicked() {
+                    set = bpm;
+                }
+                ui.end_row();
+            });
+        });
+        if set.is_some() || cancel || modal.should_close() {
+            self.open = false;
+        }
+        set
+    }
+}
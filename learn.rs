@@ -0,0 +1,166 @@
+use std::collections::VecDeque;
+
+// everything an incoming MIDI message can be bound to
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Tap,
+    Start,
+    Stop,
+    NudgeUp,
+    NudgeDown,
+    NextTab,
+    PrevTab,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::Tap,
+        Action::Start,
+        Action::Stop,
+        Action::NudgeUp,
+        Action::NudgeDown,
+        Action::NextTab,
+        Action::PrevTab,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Tap => "Tap",
+            Action::Start => "Start",
+            Action::Stop => "Stop",
+            Action::NudgeUp => "Nudge +1",
+            Action::NudgeDown => "Nudge -1",
+            Action::NextTab => "Next tab",
+            Action::PrevTab => "Previous tab",
+        }
+    }
+
+    // config spelling
+    fn key(self) -> &'static str {
+        match self {
+            Action::Tap => "tap",
+            Action::Start => "start",
+            Action::Stop => "stop",
+            Action::NudgeUp => "nudge_up",
+            Action::NudgeDown => "nudge_down",
+            Action::NextTab => "next_tab",
+            Action::PrevTab => "prev_tab",
+        }
+    }
+}
+
+// channels are 0 based here, 1 based when shown
+#[derive(Clone, Copy, PartialEq)]
+pub enum Trigger {
+    Note(u8, u8),
+    Cc(u8, u8),
+    Pc(u8, u8),
+}
+
+impl Trigger {
+    // only the press: note on, CC at 64 and up, any program change
+    pub fn from_msg(msg: &[u8]) -> Option<Self> {
+        let (&status, data) = msg.split_first()?;
+        let channel = status & 0x0F;
+        match (status & 0xF0, data) {
+            (0x90, [note, vel, ..]) if *vel > 0 => Some(Trigger::Note(channel, *note)),
+            (0xB0, [cc, value, ..]) if *value >= 64 => Some(Trigger::Cc(channel, *cc)),
+            (0xC0, [program, ..]) => Some(Trigger::Pc(channel, *program)),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Trigger::Note(ch, n) => format!("Note {} ch {}", n, ch + 1),
+            Trigger::Cc(ch, n) => format!("CC {} ch {}", n, ch + 1),
+            Trigger::Pc(ch, n) => format!("PC {} ch {}", n, ch + 1),
+        }
+    }
+
+    fn to_config(self) -> String {
+        match self {
+            Trigger::Note(ch, n) => format!("note {} {}", ch, n),
+            Trigger::Cc(ch, n) => format!("cc {} {}", ch, n),
+            Trigger::Pc(ch, n) => format!("pc {} {}", ch, n),
+        }
+    }
+
+    fn from_config(kind: &str, ch: &str, n: &str) -> Option<Self> {
+        let (ch, n) = (ch.parse().ok()?, n.parse().ok()?);
+        match kind {
+            "note" => Some(Trigger::Note(ch, n)),
+            "cc" => Some(Trigger::Cc(ch, n)),
+            "pc" => Some(Trigger::Pc(ch, n)),
+            _ => None,
+        }
+    }
+}
+
+// shared between the input callback and the gui
+pub struct Learn {
+    pub bindings: Vec<(Trigger, Action)>,
+    // the next trigger to come in gets bound to this
+    pub learning: Option<Action>,
+    // matched actions waiting for the gui
+    pub pending: VecDeque<Action>,
+    // bindings changed and want saving
+    pub dirty: bool,
+}
+
+impl Learn {
+    pub fn new() -> Self {
+        Self { bindings: Vec::new(), learning: None, pending: VecDeque::new(), dirty: false }
+    }
+
+    // `map = <action> <note|cc|pc> <channel> <number>` lines
+    pub fn load(&mut self, lines: &[&str]) {
+        for line in lines {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let [action, kind, ch, n] = words[..] else {
+                continue;
+            };
+            let action = Action::ALL.into_iter().find(|a| a.key() == action);
+            if let (Some(action), Some(trigger)) = (action, Trigger::from_config(kind, ch, n)) {
+                self.bindings.push((trigger, action));
+            }
+        }
+    }
+
+    pub fn save(&self) -> Vec<String> {
+        self.bindings
+            .iter()
+            .map(|(t, a)| format!("{} {}", a.key(), t.to_config()))
+            .collect()
+    }
+
+    // returns whether the gui has something to do
+    pub fn handle(&mut self, msg: &[u8]) -> bool {
+        let Some(trigger) = Trigger::from_msg(msg) else {
+            return false;
+        };
+        if let Some(action) = self.learning.take() {
+            // one trigger, one action
+            self.bindings.retain(|(t, _)| *t != trigger);
+            self.bindings.push((trigger, action));
+            self.dirty = true;
+            return true;
+        }
+        let before = self.pending.len();
+        for (t, a) in &self.bindings {
+            if *t == trigger {
+                self.pending.push_back(*a);
+            }
+        }
+        self.pending.len() > before
+    }
+
+    pub fn triggers(&self, action: Action) -> Vec<Trigger> {
+        self.bindings.iter().filter(|(_, a)| *a == action).map(|(t, _)| *t).collect()
+    }
+
+    pub fn clear(&mut self, action: Action) {
+        self.bindings.retain(|(_, a)| *a != action);
+        self.dirty = true;
+    }
+}
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::Path;
+
+pub struct Track {
+    pub artist: String,
+    pub title: String,
+    pub bpm: f64,
+}
+
+impl Track {
+    pub fn name(&self) -> String {
+        match self.artist.is_empty() {
+            true => self.title.clone(),
+            false => format!("{} - {}", self.artist, self.title),
+        }
+    }
+}
+
+// a DJ library's tracks that have a tempo: a rekordbox XML export, or a Mixxx
+// library or playlist exported as CSV
+pub fn load(path: &Path) -> Result<Vec<Track>, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+    let tracks = match ext.as_deref() {
+        Some("xml") => rekordbox(&text)?,
+        Some("csv") => mixxx(&text)?,
+        _ => return Err("Not a rekordbox XML or Mixxx CSV export".to_string()),
+    };
+    if tracks.is_empty() {
+        return Err("No tracks with a BPM".to_string());
+    }
+    Ok(tracks)
+}
+
+// every word somewhere in the artist or title, any case
+pub fn search<'a>(tracks: &'a [Track], query: &str) -> impl Iterator<Item = &'a Track> {
+    let words: Vec<String> = query.split_whitespace().map(str::to_lowercase).collect();
+    tracks.iter().filter(move |t| {
+        let name = t.name().to_lowercase();
+        words.iter().all(|w| name.contains(w.as_str()))
+    })
+}
+
+// the collection's <TRACK> elements. playlists list tracks again by key only, with
+// no AverageBpm, so they drop out
+fn rekordbox(text: &str) -> Result<Vec<Track>, String> {
+    if !text.contains("<DJ_PLAYLISTS") {
+        return Err("Not a rekordbox collection".to_string());
+    }
+    let mut tracks = Vec::new();
+    for element in text.split("<TRACK").skip(1) {
+        let Some(end) = element.find('>') else { continue };
+        let attributes = &element[..end];
+        let Some(bpm) = attribute(attributes, "AverageBpm").and_then(|b| b.parse::<f64>().ok()) else { continue };
+        if bpm <= 0.0 {
+            continue;
+        }
+        tracks.push(Track {
+            artist: attribute(attributes, "Artist").unwrap_or_default(),
+            title: attribute(attributes, "Name").unwrap_or_default(),
+            bpm,
+        });
+    }
+    Ok(tracks)
+}
+
+fn attribute(attributes: &str, name: &str) -> Option<String> {
+    let start = attributes.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let len = attributes[start..].find('"')?;
+    Some(unescape(&attributes[start..start + len]))
+}
+
+fn unescape(text: &str) -> String {
+    let mut out = String::new();
+    let mut rest = text;
+    while let Some(at) = rest.find('&') {
+        out += &rest[..at];
+        rest = &rest[at..];
+        let Some(end) = rest.find(';') else { break };
+        let entity = &rest[1..end];
+        let c = match entity {
+            "amp" => Some('&'),
+            "lt" => Some('<'),
+            "gt" => Some('>'),
+            "quot" => Some('"'),
+            "apos" => Some('\''),
+            _ => match entity.strip_prefix("#x").or_else(|| entity.strip_prefix("#X")) {
+                Some(hex) => u32::from_str_radix(hex, 16).ok().and_then(char::from_u32),
+                None => entity.strip_prefix('#').and_then(|d| d.parse().ok()).and_then(char::from_u32),
+            },
+        };
+        match c {
+            Some(c) => {
+                out.push(c);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('&');
+                rest = &rest[1..];
+            }
+        }
+    }
+    out + rest
+}
+
+// columns found by the header, which follows the library view's column order
+fn mixxx(text: &str) -> Result<Vec<Track>, String> {
+    let mut rows = text.lines().filter(|l| !l.trim().is_empty()).map(csv_row);
+    let header = rows.next().unwrap_or_default();
+    let column = |name: &str| header.iter().position(|h| h.trim().eq_ignore_ascii_case(name));
+    let Some(bpm) = column("BPM") else {
+        return Err("No BPM column".to_string());
+    };
+    let (artist, title) = (column("Artist"), column("Title"));
+    let field = |row: &[String], column: Option<usize>| column.and_then(|c| row.get(c)).cloned().unwrap_or_default();
+    Ok(rows
+        .filter_map(|row| {
+            let bpm = row.get(bpm)?.trim().parse::<f64>().ok().filter(|&b| b > 0.0)?;
+            Some(Track { artist: field(&row, artist), title: field(&row, title), bpm })
+        })
+        .collect())
+}
+
+// quoted fields may hold commas and doubled quotes, not line breaks
+fn csv_row(line: &str) -> Vec<String> {
+    let mut fields = vec![String::new()];
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                chars.next();
+                fields.last_mut().unwrap().push('"');
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(String::new()),
+            c => fields.last_mut().unwrap().push(c),
+        }
+    }
+    fields
+}
@@ -0,0 +1,168 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs, UdpSocket};
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+use crate::clock::Clock;
+
+const WLED_PORT: u16 = 21324;
+// how long a flash stays lit
+const FLASH: Duration = Duration::from_millis(100);
+const TIMEOUT: Duration = Duration::from_millis(300);
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Kind {
+    Hue,
+    Wled,
+}
+
+impl Kind {
+    pub const ALL: [Kind; 2] = [Kind::Hue, Kind::Wled];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Kind::Hue => "Hue",
+            Kind::Wled => "WLED",
+        }
+    }
+}
+
+pub struct Fixture {
+    pub kind: Kind,
+    pub enabled: bool,
+    // Hue bridge or WLED controller
+    pub address: String,
+    // Hue only: the bridge's API user name and the light's number
+    pub user: String,
+    pub light: u32,
+    // WLED only
+    pub leds: u32,
+    pub downbeat_only: bool,
+}
+
+impl Fixture {
+    pub fn new() -> Self {
+        Self {
+            kind: Kind::Wled,
+            enabled: true,
+            address: String::new(),
+            user: String::new(),
+            light: 1,
+            leds: 30,
+            downbeat_only: false,
+        }
+    }
+}
+
+// flashes smart lights on the beat, the downbeat in its own color
+pub struct Lights {
+    pub fixtures: Vec<Fixture>,
+    pub beat: [u8; 3],
+    pub downbeat: [u8; 3],
+}
+
+impl Lights {
+    pub fn new() -> Self {
+        Self { fixtures: Vec::new(), beat: [255, 255, 255], downbeat: [255, 40, 0] }
+    }
+}
+
+// network calls can take a while, so they get their own thread. send true for a
+// downbeat; it ends when the sender is dropped
+pub fn spawn(clock: Arc<Clock>) -> Sender<bool> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || flasher(clock, rx));
+    tx
+}
+
+fn flasher(clock: Arc<Clock>, rx: Receiver<bool>) {
+    let socket = UdpSocket::bind("0.0.0.0:0").ok();
+    while let Ok(mut downbeat) = rx.recv() {
+        // slow bridges fall behind, only the latest beat matters
+        while let Ok(d) = rx.try_recv() {
+            downbeat = d;
+        }
+        // copy out so the lock isn't held across the network
+        let (targets, color) = {
+            let lights = clock.lights.lock().unwrap();
+            let color = if downbeat { lights.downbeat } else { lights.beat };
+            let targets: Vec<(Kind, String, String, u32, u32)> = lights
+                .fixtures
+                .iter()
+                .filter(|f| f.enabled && !f.address.is_empty() && (downbeat || !f.downbeat_only))
+                .map(|f| (f.kind, f.address.clone(), f.user.clone(), f.light, f.leds))
+                .collect();
+            (targets, color)
+        };
+        if targets.is_empty() {
+            continue;
+        }
+        for on in [true, false] {
+            for (kind, address, user, light, leds) in &targets {
+                match kind {
+                    Kind::Hue => hue(address, user, *light, on.then_some(color)),
+                    Kind::Wled => {
+                        if let Some(socket) = &socket {
+                            wled(socket, address, *leds, if on { color } else { [0, 0, 0] });
+                        }
+                    }
+                }
+            }
+            if on {
+                thread::sleep(FLASH);
+            }
+        }
+    }
+}
+
+// DRGB realtime packet, WLED goes back to its own effect 2 seconds after the last one
+fn wled(socket: &UdpSocket, address: &str, leds: u32, color: [u8; 3]) {
+    let mut packet = vec![2, 2];
+    for _ in 0..leds.clamp(1, 490) {
+        packet.extend(color);
+    }
+    let _ = socket.send_to(&packet, (address, WLED_PORT));
+}
+
+// plain HTTP to the bridge's v1 API, None turns the light off
+fn hue(address: &str, user: &str, light: u32, color: Option<[u8; 3]>) {
+    let body = match color {
+        Some(rgb) => {
+            let (x, y) = xy(rgb);
+            format!("{{\"on\":true,\"bri\":254,\"xy\":[{:.4},{:.4}],\"transitiontime\":0}}", x, y)
+        }
+        None => "{\"on\":false,\"transitiontime\":0}".to_string(),
+    };
+    let request = format!(
+        "PUT /api/{}/lights/{}/state HTTP/1.1\r\nHost: {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        user,
+        light,
+        address,
+        body.len(),
+        body,
+    );
+    let Some(addr) = (address, 80).to_socket_addrs().ok().and_then(|mut a| a.next()) else {
+        return;
+    };
+    if let Ok(mut stream) = TcpStream::connect_timeout(&addr, TIMEOUT) {
+        let _ = stream.set_read_timeout(Some(TIMEOUT));
+        if stream.write_all(request.as_bytes()).is_ok() {
+            // the reply doesn't matter, but reading it lets the bridge close cleanly
+            let _ = stream.read(&mut [0; 512]);
+        }
+    }
+}
+
+// sRGB to the CIE xy Hue wants
+fn xy(rgb: [u8; 3]) -> (f64, f64) {
+    let [r, g, b] = rgb.map(|c| {
+        let c = c as f64 / 255.0;
+        if c > 0.04045 { ((c + 0.055) / 1.055).powf(2.4) } else { c / 12.92 }
+    });
+    let x = r * 0.4124 + g * 0.3576 + b * 0.1805;
+    let y = r * 0.2126 + g * 0.7152 + b * 0.0722;
+    let z = r * 0.0193 + g * 0.1192 + b * 0.9505;
+    let sum = x + y + z;
+    if sum == 0.0 { (0.3127, 0.3290) } else { (x / sum, y / sum) }
+}
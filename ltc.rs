@@ -0,0 +1,302 @@
+use std::sync::Arc;
+use std::time::Instant;
+use crate::clock::Clock;
+use crate::reclock;
+
+// peak level, LTC is usually recorded well below full scale
+const AMPLITUDE: f32 = 0.5;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Fps {
+    F24,
+    F25,
+    F2997Drop,
+    F30,
+}
+
+impl Fps {
+    pub const ALL: [Fps; 4] = [Fps::F24, Fps::F25, Fps::F2997Drop, Fps::F30];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Fps::F24 => "24",
+            Fps::F25 => "25",
+            Fps::F2997Drop => "29.97 drop",
+            Fps::F30 => "30",
+        }
+    }
+
+    pub fn rate(self) -> f64 {
+        match self {
+            Fps::F24 => 24.0,
+            Fps::F25 => 25.0,
+            Fps::F2997Drop => 30000.0 / 1001.0,
+            Fps::F30 => 30.0,
+        }
+    }
+
+    // frames counted per second in the timecode itself
+    pub fn nominal(self) -> u64 {
+        match self {
+            Fps::F24 => 24,
+            Fps::F25 => 25,
+            Fps::F2997Drop | Fps::F30 => 30,
+        }
+    }
+
+    // hh:mm:ss:ff of the nth frame
+    pub fn timecode(self, mut n: u64) -> [u64; 4] {
+        if self == Fps::F2997Drop {
+            // frames 0 and 1 are skipped every minute except every tenth
+            let (d, m) = (n / 17982, n % 17982);
+            n += 18 * d + if m >= 2 { 2 * ((m - 2) / 1798) } else { 0 };
+        }
+        let nom = self.nominal();
+        [n / (nom * 3600) % 24, n / (nom * 60) % 60, n / nom % 60, n % nom]
+    }
+
+    // frames in an hour of real time
+    fn per_hour(self) -> u64 {
+        match self {
+            Fps::F2997Drop => 107892,
+            _ => self.nominal() * 3600,
+        }
+    }
+
+    // the 80 bits of one frame, least significant first
+    pub fn bits(self, n: u64) -> [bool; 80] {
+        let [h, m, s, f] = self.timecode(n);
+        let mut bits = [false; 80];
+        let mut put = |at: usize, len: usize, value: u64| {
+            for i in 0..len {
+                bits[at + i] = value >> i & 1 == 1;
+            }
+        };
+        put(0, 4, f % 10);
+        put(8, 2, f / 10);
+        put(10, 1, (self == Fps::F2997Drop) as u64);
+        put(16, 4, s % 10);
+        put(24, 3, s / 10);
+        put(32, 4, m % 10);
+        put(40, 3, m / 10);
+        put(48, 4, h % 10);
+        put(56, 2, h / 10);
+        // sync word
+        put(64, 16, 0b1011_1111_1111_1100);
+        // the polarity bit keeps every frame starting on the same edge
+        let polarity = if self == Fps::F25 { 59 } else { 27 };
+        bits[polarity] = bits.iter().filter(|&&b| b).count() % 2 == 1;
+        bits
+    }
+}
+
+// linear timecode on one channel of the audio output, following a clock's transport
+pub struct Ltc {
+    // None is off
+    pub source: Option<Arc<Clock>>,
+    pub fps: Fps,
+    // 0 based
+    pub channel: usize,
+    // what the song's start reads as, 01:00:00:00 is common
+    pub start_hour: u64,
+    // half bit last rendered, the output level and the bits of the current frame
+    slot: Option<u64>,
+    level: f32,
+    frame: Option<(u64, [bool; 80])>,
+}
+
+impl Ltc {
+    pub fn new() -> Self {
+        Self { source: None, fps: Fps::F25, channel: 0, start_hour: 1, slot: None, level: AMPLITUDE, frame: None }
+    }
+
+    // `t` is seconds since the start of the song, None while stopped
+    pub fn sample(&mut self, t: Option<f64>) -> f32 {
+        let Some(t) = t.filter(|t| *t >= 0.0) else {
+            self.slot = None;
+            return 0.0;
+        };
+        // biphase mark: the level flips at every bit and in the middle of a one
+        let slot = (t * self.fps.rate() * 160.0) as u64;
+        if self.slot != Some(slot) {
+            let n = slot / 160 + self.start_hour * self.fps.per_hour();
+            let bits = match self.frame {
+                Some((frame, bits)) if frame == n => bits,
+                _ => {
+                    let bits = self.fps.bits(n);
+                    self.frame = Some((n, bits));
+                    bits
+                }
+            };
+            if slot.is_multiple_of(2) || bits[(slot / 2 % 80) as usize] {
+                self.level = -self.level;
+            }
+            self.slot = Some(slot);
+        }
+        self.level
+    }
+}
+
+// the sync word as it sits in bits 64..80
+const SYNC: u128 = 0b1011_1111_1111_1100;
+// how hard each frame pulls on the chase, like the MIDI clock PLL
+const ALPHA: f64 = 0.1;
+const BETA: f64 = 0.01;
+// frames before the chase trusts the rate, about half a second
+const LOCK_FRAMES: u32 = 12;
+// further off than this is a jump in the timecode, start over
+const JUMP: f64 = 0.2;
+// timecode gone for this long stops the transport
+const DROPOUT: f64 = 0.25;
+
+// turns incoming LTC audio back into timecode frames
+pub struct Decoder {
+    rate: f64,
+    // samples per bit, tracked as the tape speed moves
+    bit: f64,
+    since: f64,
+    high: bool,
+    // a short interval waits here for its partner to make a one
+    half: bool,
+    window: u128,
+}
+
+impl Decoder {
+    pub fn new(rate: f64) -> Self {
+        // between 24 and 30 fps to start with
+        Self { rate, bit: rate / 2160.0, since: 0.0, high: false, half: false, window: 0 }
+    }
+
+    // returns the timecode and drop flag of a frame that ended on this sample
+    pub fn sample(&mut self, s: f32) -> Option<([u64; 4], bool)> {
+        self.since += 1.0;
+        // a little hysteresis against noise around zero
+        let high = if self.high { s > -0.05 } else { s > 0.05 };
+        if high == self.high {
+            // no transitions for a while means no signal
+            if self.since > self.bit * 4.0 {
+                self.half = false;
+            }
+            return None;
+        }
+        self.high = high;
+        let d = std::mem::replace(&mut self.since, 0.0);
+        if d < self.bit * 0.3 || d > self.bit * 2.0 {
+            self.half = false;
+            return None;
+        }
+        let bit = if d < self.bit * 0.75 {
+            self.bit += (d * 2.0 - self.bit) * 0.1;
+            if !self.half {
+                self.half = true;
+                return None;
+            }
+            self.half = false;
+            1
+        } else {
+            self.bit += (d - self.bit) * 0.1;
+            self.half = false;
+            0
+        };
+        self.window = (self.window >> 1) | (bit << 79);
+        if self.window >> 64 != SYNC {
+            return None;
+        }
+        let w = self.window;
+        let field = |at: u32, len: u32| ((w >> at) & ((1 << len) - 1)) as u64;
+        let tc = [
+            field(56, 2) * 10 + field(48, 4),
+            field(40, 3) * 10 + field(32, 4),
+            field(24, 3) * 10 + field(16, 4),
+            field(8, 2) * 10 + field(0, 4),
+        ];
+        Some((tc, field(10, 1) == 1))
+    }
+
+    // roughly, from the bit length
+    pub fn fps(&self, drop: bool) -> Fps {
+        let fps = self.rate / (self.bit * 80.0);
+        if drop {
+            Fps::F2997Drop
+        } else if fps < 24.5 {
+            Fps::F24
+        } else if fps < 27.5 {
+            Fps::F25
+        } else {
+            Fps::F30
+        }
+    }
+}
+
+impl Fps {
+    // real seconds since 00:00:00:00
+    pub fn secs(self, [h, m, s, f]: [u64; 4]) -> f64 {
+        if self == Fps::F2997Drop {
+            let minutes = h * 60 + m;
+            let frames = (minutes * 60 + s) * 30 + f - 2 * (minutes - minutes / 10);
+            frames as f64 * 1001.0 / 30000.0
+        } else {
+            ((h * 60 + m) * 60 + s) as f64 + f as f64 / self.nominal() as f64
+        }
+    }
+}
+
+// incoming timecode smoothed into a position and a speed for the clock to follow
+pub struct Chase {
+    // the timecode the song starts at, in hours
+    pub start_hour: u64,
+    pub channel: usize,
+    pub timecode: Option<[u64; 4]>,
+    pub fps: Option<Fps>,
+    // smoothed seconds of timecode at an instant, and timecode seconds per second
+    anchor: Option<(Instant, f64)>,
+    speed: f64,
+    frames: u32,
+}
+
+impl Chase {
+    pub fn new() -> Self {
+        Self { start_hour: 1, channel: 0, timecode: None, fps: None, anchor: None, speed: 1.0, frames: 0 }
+    }
+
+    // a frame that ended at `at`
+    pub fn frame(&mut self, at: Instant, timecode: [u64; 4], fps: Fps) {
+        // the frame's timecode is when it started
+        let secs = fps.secs(timecode) + 1.0 / fps.rate();
+        self.timecode = Some(timecode);
+        self.fps = Some(fps);
+        match self.anchor {
+            Some((t, s)) if at > t => {
+                let dt = reclock::secs_between(t, at);
+                let predicted = s + dt * self.speed;
+                let err = secs - predicted;
+                if err.abs() > JUMP {
+                    self.reset(at, secs);
+                    return;
+                }
+                self.anchor = Some((at, predicted + err * ALPHA));
+                self.speed = (self.speed + err * BETA / dt.max(0.001)).clamp(0.5, 2.0);
+                self.frames += 1;
+            }
+            _ => self.reset(at, secs),
+        }
+    }
+
+    fn reset(&mut self, at: Instant, secs: f64) {
+        self.anchor = Some((at, secs));
+        self.speed = 1.0;
+        self.frames = 0;
+    }
+
+    // when the song's start was at the current speed, and the speed. None
+    // without a steady signal or before the start hour
+    pub fn lock_point(&self, now: Instant) -> Option<(Instant, f64)> {
+        let (t, s) = self.anchor?;
+        if self.frames < LOCK_FRAMES || reclock::secs_between(t, now) > DROPOUT {
+            return None;
+        }
+        let song = s - (self.start_hour * 3600) as f64;
+        let zero = reclock::shift(t, -song / self.speed);
+        (zero <= now).then_some((zero, self.speed))
+    }
+}
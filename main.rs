@@ -1,218 +1,2849 @@
-#![windows_subsystem = "windows"]
-
-use eframe::egui;
-use std::fs;
-use std::sync::{Arc};
-use std::sync::atomic::{AtomicI32, AtomicUsize, Ordering};
-use std::thread;
-use std::time::{Instant, Duration};
-use midir::MidiOutput;
-
-fn main() -> eframe::Result<()> {
-    let viewport = egui::ViewportBuilder::default()
-        .with_inner_size([300.0, 155.0]);
-    let options = eframe::NativeOptions {
-        viewport,
-        ..Default::default()
-    };
-
-    let midiot = MidiOutput::new("Rust Midi Output").expect("Failed to create MIDI output");
-    let outports = midiot.ports();
-
-    if outports.is_empty() {
-        println!("No MIDI output ports found");
-        return Ok(());
-    }
-
-    // Gather available port names for GUI dropdown
-    let guiout: Vec<String> = outports.iter()
-        .map(|p| midiot.port_name(p).unwrap_or("Unknown".to_string()))
-        .collect();
-
-    let bpm = Arc::new(AtomicI32::new(0));
-    let guibpm = Arc::clone(&bpm);
-
-    let sharedmidiport = Arc::new(AtomicUsize::new(1));
-    let guimidiport = Arc::clone(&sharedmidiport);
-
-    // Spawn MIDI clock thread
-    let threadbpm = Arc::clone(&bpm);
-    let threadmidiport = Arc::clone(&sharedmidiport);
-    thread::spawn(move || {
-        let mut conn_out: Option<midir::MidiOutputConnection> = None;
-        let mut oldval = usize::MAX;
-        let mut next_tick = Instant::now();
-
-        loop {
-            let val = threadbpm.load(Ordering::SeqCst);
-            let indval = threadmidiport.load(Ordering::SeqCst);
-
-            // reconnect only if port changed or connection lost
-            if indval != oldval || conn_out.is_none() {
-                conn_out = None; // close any existing connection
-                if indval < outports.len() {
-                    let port = &outports[indval]; // skip port 0
-                    match MidiOutput::new("Rust Midi Output Thread").unwrap().connect(port, "midir-selected") {
-                        Ok(c) => {
-                            conn_out = Some(c);
-                            oldval = indval;
-                        }
-                        Err(e) => {
-                            eprintln!("Failed to connect to port {}: {}", indval + 1, e);
-                            conn_out = None;
-                            thread::sleep(Duration::from_secs(1));
-                            continue;
-                        }
-                    }
-                }
-            }
-
-            if val == 0 || conn_out.is_none() {
-                thread::sleep(Duration::from_millis(500));
-                continue;
-            }
-
-            let interval_ms = 60000.0 / (val as f64 * 24.0);
-            let interval = Duration::from_secs_f64(interval_ms / 1000.0);
-
-            let now = Instant::now();
-            if now < next_tick {
-                thread::sleep(next_tick - now);
-            }
-
-            if let Some(conn) = conn_out.as_mut() {
-                let _ = conn.send(&[0xF8]);
-            }
-
-            next_tick += interval;
-        }
-    });
-
-    // Launch GUI
-    eframe::run_native(
-        "MidiClock",
-        options,
-        Box::new(|cc| Ok(Box::new(MyApp::new(cc, guibpm, guimidiport, guiout)))),
-    )
-}
-
-struct MyApp {
-    bpm: Arc<AtomicI32>,
-    last_press: Option<Instant>,
-    dropdown_index: Arc<AtomicUsize>,
-    parrot_names: Vec<String>,
-    current_index: usize,
-    impact_font: eframe::egui::FontId,
-}
-
-impl MyApp {
-    fn new(
-        cc: &eframe::CreationContext<'_>,
-        bpm: Arc<AtomicI32>,
-        dropdown_index: Arc<AtomicUsize>,
-        parrot_names: Vec<String>,
-    ) -> Self {
-        let font_data = fs::read(r"C:\Windows\Fonts\Impact.ttf").expect("Failed to read Impact.ttf");
-        let mut fonts = egui::FontDefinitions::default();
-        fonts.font_data.insert(
-            "ImpactFont".to_owned(),
-            egui::FontData::from_owned(font_data).into(),
-        );
-        fonts
-            .families
-            .insert(egui::FontFamily::Name("BPM".into()), vec!["ImpactFont".to_owned()]);
-        cc.egui_ctx.set_fonts(fonts);
-        let impact_font = egui::FontId::new(90.0, egui::FontFamily::Name("BPM".into()));
-
-        Self {
-            bpm,
-            last_press: None,
-            dropdown_index,
-            parrot_names,
-            current_index: 0,
-            impact_font,
-        }
-    }
-}
-
-impl eframe::App for MyApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        if ctx.input(|i| i.key_pressed(egui::Key::Space)) {
-            let now = Instant::now();
-            if let Some(last) = self.last_press {
-                let elapsed = now.duration_since(last);
-                let interval_secs = elapsed.as_secs_f32();
-                if interval_secs > 0.0 {
-                    let bpm = (60.0 / interval_secs).round() as u32;
-                    if bpm >= 40 && bpm <= 300 {
-                        self.bpm.store(bpm.try_into().unwrap(), Ordering::SeqCst);
-                    }
-                }
-            }
-            self.last_press = Some(now);
-        }
-        let mut bpm = self.bpm.load(Ordering::SeqCst);
-        if ctx.input(|i| i.key_pressed(egui::Key::ArrowUp)) {
-            
-            if bpm < 300 {
-                bpm += 1;
-                self.bpm.store(bpm, Ordering::SeqCst);
-            }
-        }
-
-        if ctx.input(|i| i.key_pressed(egui::Key::ArrowRight)) {
-            
-            if bpm <= 290 {
-                bpm += 10;
-                self.bpm.store(bpm, Ordering::SeqCst);
-            }
-        }
-
-        if ctx.input(|i| i.key_pressed(egui::Key::ArrowDown)) {
-           
-            if bpm > 40 {
-                bpm -= 1;
-                self.bpm.store(bpm, Ordering::SeqCst);
-            }
-        }
-
-        if ctx.input(|i| i.key_pressed(egui::Key::ArrowLeft)) {
-           
-            if bpm >= 50 {
-                bpm -= 10;
-                self.bpm.store(bpm, Ordering::SeqCst);
-            }
-        }
-
-
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical_centered(|ui| {
-                let value = self.bpm.load(Ordering::SeqCst);
-                if value != 0 {
-                    ui.label(
-                        eframe::egui::RichText::new(format!("{}", value))
-                            .font(self.impact_font.clone()),
-                    );
-                } else {
-                    ui.label(
-                        eframe::egui::RichText::new("--").font(self.impact_font.clone()),
-                    );
-                }
-
-                ui.separator();
-                ui.horizontal_centered(|ui| {
-                egui::ComboBox::from_label("")
-                    .selected_text(self.parrot_names[self.current_index].clone())
-                    .show_ui(ui, |ui| {
-                        for (index, name) in self.parrot_names.iter().enumerate().skip(1) {
-                            if ui.selectable_label(self.current_index == index, name.clone()).clicked() {
-                                self.current_index = index;
-                                self.dropdown_index.store(index, Ordering::SeqCst);
-                            }
-                        }
-                    });
-                });
-            });
-        });
-    }
-}
+#![windows_subsystem = "windows"]
+
+mod audio;
+mod automation;
+mod awake;
+mod beat;
+mod bench;
+mod capture;
+mod clock;
+mod config;
+mod connect;
+mod crash;
+mod cues;
+#[cfg(target_os = "linux")]
+mod dbus;
+mod dmx;
+#[cfg(feature = "gpio")]
+mod gpio;
+mod history;
+mod keypad;
+mod learn;
+mod library;
+mod lights;
+mod ltc;
+mod marking;
+mod metrics;
+mod monitor;
+mod mqtt;
+mod msc;
+mod net;
+mod palette;
+#[cfg(windows)]
+mod pipe;
+mod profile;
+mod reclock;
+mod repeat;
+mod schedule;
+mod session;
+mod smf;
+mod tap;
+mod testmode;
+mod time;
+mod watchdog;
+mod wav;
+
+use eframe::egui;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::{Instant, Duration, SystemTime};
+use midir::{Ignore, MidiInput, MidiInputConnection, MidiInputPort, MidiOutput, MidiOutputPort};
+use audio::AudioClock;
+use automation::Point;
+use config::Config;
+use clock::{Clock, Request, Sleep, Source, BEATS_PER_BAR, TICKS_PER_BAR};
+use cues::Cue;
+use history::{ClockState, History};
+use keypad::Keypad;
+use learn::{Action, Learn};
+use marking::Markings;
+use monitor::Monitor;
+use palette::{Command, Palette, Window};
+use profile::Profile;
+use reclock::Pll;
+use repeat::Repeat;
+use schedule::Timer;
+
+fn main() -> eframe::Result<()> {
+    let args: Vec<String> = std::env::args().collect();
+    if args.get(1).is_some_and(|a| a == "--bench") {
+        bench::main(&args);
+        return Ok(());
+    }
+
+    crash::install();
+
+    let viewport = egui::ViewportBuilder::default()
+        .with_inner_size([420.0, 215.0]);
+    let options = eframe::NativeOptions {
+        viewport,
+        ..Default::default()
+    };
+
+    let midiot = MidiOutput::new("Rust Midi Output").expect("Failed to create MIDI output");
+    let outports = midiot.ports();
+
+    if outports.is_empty() {
+        println!("No MIDI output ports found");
+        return Ok(());
+    }
+
+    // Gather available port names for GUI dropdown
+    let guiout: Vec<String> = outports.iter()
+        .map(|p| midiot.port_name(p).unwrap_or("Unknown".to_string()))
+        .collect();
+
+    // Launch GUI
+    eframe::run_native(
+        "MidiClock",
+        options,
+        Box::new(|cc| Ok(Box::new(MyApp::new(cc, outports, guiout)))),
+    )
+}
+
+// one independent clock, with its own thread, port, tempo and transport
+struct Tab {
+    name: String,
+    clock: Arc<Clock>,
+    tapper: tap::Tapper,
+    // tapped tempo waiting to be used, and how many taps in a row it has held
+    tapped: Option<(f64, u32)>,
+    // A/B tempos, which one is on, and beats to glide between them (0 jumps)
+    slots: [i32; 2],
+    slot: usize,
+    fade: u32,
+    // which ports were on when last looked, to notice one being picked
+    enabled: Vec<bool>,
+    // the clock's `moved` count when last looked
+    moved: u64,
+}
+
+struct MyApp {
+    tabs: Vec<Tab>,
+    tab: usize,
+    tab_count: usize,
+    outports: Vec<MidiOutputPort>,
+    parrot_names: Vec<String>,
+    // each output port's device quirks, by index like the names
+    profiles: Vec<Profile>,
+    // the tempo each port was last used at, saved a little after it settles
+    port_tempos: Vec<Option<i32>>,
+    port_tempos_changed: Option<Instant>,
+    // a port just picked and the tempo it was last at, until used or dismissed
+    recall: Option<(usize, i32)>,
+    impact_font: eframe::egui::FontId,
+    monitor: Arc<Mutex<Monitor>>,
+    show_monitor: bool,
+    in_ports: Vec<MidiInputPort>,
+    in_names: Vec<String>,
+    in_index: Option<usize>,
+    in_conn: Option<MidiInputConnection<()>>,
+    pll: Arc<Mutex<Pll>>,
+    audio_clock: Arc<AudioClock>,
+    audio_stream: Option<cpal::Stream>,
+    audio_error: Option<String>,
+    ltc: Arc<Mutex<ltc::Ltc>>,
+    chase: Arc<Mutex<ltc::Chase>>,
+    input_stream: Option<cpal::Stream>,
+    beats: Arc<Mutex<beat::Detector>>,
+    // last step the detected tempo pulled the clock by
+    steered: Instant,
+    follower: Arc<Mutex<net::Follower>>,
+    net_listener: Option<net::Listener>,
+    net_error: Option<String>,
+    // progress or result of the last dropped file's tempo analysis
+    analysis: Arc<Mutex<Option<String>>>,
+    // tempo changes from the last dropped MIDI file, until used or dismissed
+    tempo_map: Option<Vec<(f64, f64)>>,
+    awake: awake::Awake,
+    // hold off system and display sleep while any tab runs
+    keep_awake: bool,
+    // bars in a phrase, for the countdown to the next one
+    phrase_bars: u64,
+    show_settings: bool,
+    show_automation: bool,
+    show_cues: bool,
+    show_scheduler: bool,
+    show_conductor: bool,
+    // tracks from a DJ library export, the search as typed, and how the import went
+    show_library: bool,
+    library: Vec<library::Track>,
+    library_search: String,
+    library_status: Option<String>,
+    show_slots: bool,
+    // a knob or a slider for sweeping the tempo with the mouse, and the unrounded
+    // value while the knob is being turned
+    show_sweep: bool,
+    sweep_knob: bool,
+    knob_drag: Option<f64>,
+    // touchscreen layout, fullscreen and hard to leave by accident
+    kiosk: bool,
+    // when the press on the kiosk exit began
+    kiosk_exit: Option<Instant>,
+    // ticks sent and uptime under the phrase counter
+    show_status: bool,
+    automation_grab: Option<usize>,
+    bpm_min: i32,
+    bpm_max: i32,
+    // typed tempo while the big number is being edited
+    bpm_edit: Option<String>,
+    tap_note: tap::Subdivision,
+    // taps only show their tempo until it's confirmed or settles
+    tap_preview: bool,
+    // seconds without a tap that start a fresh run
+    tap_timeout: f64,
+    tap_rounding: tap::Rounding,
+    ctx: egui::Context,
+    config: Config,
+    learn: Arc<Mutex<Learn>>,
+    show_learn: bool,
+    palette: Palette,
+    keypad: Keypad,
+    arrows: Repeat,
+    history: History<Snapshot>,
+    // where the last session export went, or why it didn't
+    export_status: Option<String>,
+    // the running capture's file or the last one's result
+    capture_status: Option<String>,
+    markings: Markings,
+    // test mode drift typed in cents rather than ppm
+    drift_cents: bool,
+    // settings profile name as typed, and how the last save or load went
+    profile_name: String,
+    profile_status: Option<String>,
+    // every tab's clock, for the metrics endpoint
+    clocks: Arc<Mutex<Vec<Arc<Clock>>>>,
+    started: Instant,
+    metrics: Option<metrics::Server>,
+    metrics_port: u16,
+    metrics_error: Option<String>,
+    // the session bus control interface, up while kept
+    #[cfg(target_os = "linux")]
+    dbus: Option<zbus::blocking::Connection>,
+    // scheduled start as typed
+    arm_time: String,
+}
+
+// a settings profile button, handled once the settings window lets go of self
+enum SettingsProfile {
+    Save(String),
+    Load(String),
+    Export(String),
+}
+
+// what undo puts back
+#[derive(Clone, PartialEq)]
+struct Snapshot {
+    clocks: Vec<ClockState>,
+    bpm_range: (i32, i32),
+    tap_note: tap::Subdivision,
+    profiles: Vec<Profile>,
+}
+
+impl MyApp {
+    fn new(
+        cc: &eframe::CreationContext<'_>,
+        outports: Vec<MidiOutputPort>,
+        parrot_names: Vec<String>,
+    ) -> Self {
+        let font_data = fs::read(r"C:\Windows\Fonts\Impact.ttf").expect("Failed to read Impact.ttf");
+        let mut fonts = egui::FontDefinitions::default();
+        fonts.font_data.insert(
+            "ImpactFont".to_owned(),
+            egui::FontData::from_owned(font_data).into(),
+        );
+        fonts
+            .families
+            .insert(egui::FontFamily::Name("BPM".into()), vec!["ImpactFont".to_owned()]);
+        cc.egui_ctx.set_fonts(fonts);
+        let impact_font = egui::FontId::new(90.0, egui::FontFamily::Name("BPM".into()));
+
+        // input ports are optional, only the monitor and re-clocking use them
+        let (in_ports, in_names) = match MidiInput::new("Rust Midi Input") {
+            Ok(midiin) => {
+                let ports = midiin.ports();
+                let names = ports.iter()
+                    .map(|p| midiin.port_name(p).unwrap_or("Unknown".to_string()))
+                    .collect();
+                (ports, names)
+            }
+            Err(e) => {
+                eprintln!("Failed to create MIDI input: {}", e);
+                (Vec::new(), Vec::new())
+            }
+        };
+
+        let config = Config::load();
+        let mut learn = Learn::new();
+        learn.load(&config.all("map"));
+        let saved: Vec<_> = config.all("profile").iter().filter_map(|v| Profile::from_config(v)).collect();
+        let profiles = parrot_names.iter()
+            .map(|name| saved.iter().find(|(port, _)| port == name).map_or(Profile::new(), |(_, p)| p.clone()))
+            .collect();
+        let markings = Markings::from_config(config.first("show_marking"), &config.all("marking"));
+        let tempos: Vec<(i32, &str)> = config.all("port_tempo").iter()
+            .filter_map(|v| v.split_once(';'))
+            .filter_map(|(bpm, port)| Some((bpm.parse().ok()?, port)))
+            .collect();
+        let port_tempos = parrot_names.iter()
+            .map(|name| tempos.iter().find(|(_, port)| port == name).map(|&(bpm, _)| bpm))
+            .collect();
+
+        let mut app = Self {
+            tabs: Vec::new(),
+            tab: 0,
+            tab_count: 0,
+            outports,
+            parrot_names,
+            profiles,
+            port_tempos,
+            port_tempos_changed: None,
+            recall: None,
+            impact_font,
+            monitor: Arc::new(Mutex::new(Monitor::new())),
+            show_monitor: false,
+            in_ports,
+            in_names,
+            in_index: None,
+            in_conn: None,
+            pll: Arc::new(Mutex::new(Pll::new())),
+            audio_clock: Arc::new(AudioClock::new()),
+            audio_stream: None,
+            audio_error: None,
+            ltc: Arc::new(Mutex::new(ltc::Ltc::new())),
+            chase: Arc::new(Mutex::new(ltc::Chase::new())),
+            input_stream: None,
+            beats: Arc::new(Mutex::new(beat::Detector::new())),
+            steered: Instant::now(),
+            follower: Arc::new(Mutex::new(net::Follower::new())),
+            net_listener: None,
+            net_error: None,
+            analysis: Arc::new(Mutex::new(None)),
+            tempo_map: None,
+            awake: awake::Awake::new(),
+            keep_awake: true,
+            phrase_bars: 16,
+            show_settings: false,
+            show_automation: false,
+            show_cues: false,
+            show_scheduler: false,
+            show_conductor: false,
+            show_library: false,
+            library: Vec::new(),
+            library_search: String::new(),
+            library_status: None,
+            show_slots: false,
+            show_sweep: false,
+            sweep_knob: true,
+            knob_drag: None,
+            kiosk: false,
+            kiosk_exit: None,
+            show_status: false,
+            automation_grab: None,
+            bpm_min: 40,
+            bpm_max: 300,
+            bpm_edit: None,
+            tap_note: tap::Subdivision::Quarter,
+            tap_preview: false,
+            tap_timeout: tap::RESTART,
+            tap_rounding: tap::Rounding::Whole,
+            ctx: cc.egui_ctx.clone(),
+            config,
+            learn: Arc::new(Mutex::new(learn)),
+            show_learn: false,
+            palette: Palette::new(),
+            keypad: Keypad::new(),
+            arrows: Repeat::new(),
+            history: History::new(),
+            export_status: None,
+            capture_status: None,
+            markings,
+            drift_cents: false,
+            profile_name: String::new(),
+            profile_status: None,
+            clocks: Arc::new(Mutex::new(Vec::new())),
+            started: Instant::now(),
+            metrics: None,
+            metrics_port: 9464,
+            metrics_error: None,
+            #[cfg(target_os = "linux")]
+            dbus: None,
+            arm_time: "20:00:00".to_string(),
+        };
+        app.add_tab();
+        if let Some(path) = app.config.first("library").map(PathBuf::from) {
+            app.import_library(path);
+        }
+        watchdog::spawn(
+            Arc::clone(&app.clocks),
+            Arc::clone(&app.pll),
+            Arc::clone(&app.chase),
+            Arc::clone(&app.follower),
+            Arc::clone(&app.audio_clock),
+            app.outports.clone(),
+        );
+        #[cfg(target_os = "linux")]
+        match dbus::serve(Arc::clone(&app.clocks), app.parrot_names.clone()) {
+            Ok(connection) => app.dbus = Some(connection),
+            Err(e) => eprintln!("{}", e),
+        }
+        #[cfg(windows)]
+        pipe::serve(Arc::clone(&app.clocks), app.parrot_names.clone());
+        // `--metrics <port>` serves from the start, for rigs nobody sits at
+        let args: Vec<String> = std::env::args().collect();
+        if let Some(i) = args.iter().position(|a| a == "--metrics") {
+            if let Some(port) = args.get(i + 1).and_then(|p| p.parse().ok()) {
+                app.metrics_port = port;
+            }
+            app.start_metrics();
+        }
+        if args.iter().any(|a| a == "--kiosk") {
+            app.set_kiosk(true);
+        }
+        // `--profile <name>` for a saved one, or a path to a profile file
+        if let Some(name) = args.iter().position(|a| a == "--profile").and_then(|i| args.get(i + 1)) {
+            let path = PathBuf::from(name);
+            let path = if path.is_file() { path } else { config::profile_path(name) };
+            app.load_settings(&path);
+            if let Some(status) = &app.profile_status {
+                eprintln!("{}", status);
+            }
+        }
+        app
+    }
+
+    fn add_tab(&mut self) {
+        self.tab_count += 1;
+        let name = format!("Clock {}", self.tab_count);
+        let clock = Arc::new(Clock::new(self.outports.len(), name.clone()));
+        let ctx = self.ctx.clone();
+        *clock.waker.lock().unwrap() = Some(Box::new(move || ctx.request_repaint()));
+        for (output, profile) in clock.outputs.iter().zip(&self.profiles) {
+            profile.apply(output);
+        }
+        clock::spawn(
+            Arc::clone(&clock),
+            Arc::clone(&self.pll),
+            Arc::clone(&self.chase),
+            Arc::clone(&self.follower),
+            Arc::clone(&self.audio_clock),
+            self.outports.clone(),
+            time::Real,
+        );
+        self.clocks.lock().unwrap().push(Arc::clone(&clock));
+        self.tabs.push(Tab {
+            name,
+            clock,
+            tapper: tap::Tapper::new(),
+            tapped: None,
+            slots: [120, 140],
+            slot: 0,
+            fade: 0,
+            enabled: vec![false; self.outports.len()],
+            moved: 0,
+        });
+        self.tab = self.tabs.len() - 1;
+    }
+
+    fn close_tab(&mut self, index: usize) {
+        let tab = self.tabs.remove(index);
+        tab.clock.close();
+        self.clocks.lock().unwrap().retain(|c| !Arc::ptr_eq(c, &tab.clock));
+        let mut ltc = self.ltc.lock().unwrap();
+        if ltc.source.as_ref().is_some_and(|c| Arc::ptr_eq(c, &tab.clock)) {
+            ltc.source = None;
+        }
+        drop(ltc);
+        if self.tab >= self.tabs.len() {
+            self.tab = self.tabs.len() - 1;
+        }
+    }
+
+    fn tabs_ui(&mut self, ui: &mut egui::Ui) {
+        let mut close = None;
+        ui.horizontal(|ui| {
+            for (index, tab) in self.tabs.iter().enumerate() {
+                ui.selectable_value(&mut self.tab, index, tab.name.as_str());
+                if self.tabs.len() > 1 && index == self.tab && ui.small_button("x").clicked() {
+                    close = Some(index);
+                }
+            }
+            if ui.small_button("+").clicked() {
+                self.add_tab();
+            }
+        });
+        if let Some(index) = close {
+            self.close_tab(index);
+        }
+    }
+
+    fn outputs_ui(&mut self, ui: &mut egui::Ui) {
+        let clock = &self.tabs[self.tab].clock;
+        let enabled: Vec<usize> = (0..clock.outputs.len())
+            .filter(|&index| clock.outputs[index].enabled.load(Ordering::SeqCst))
+            .collect();
+        let selected = match enabled.as_slice() {
+            [] => "No output".to_string(),
+            [index] => self.parrot_names[*index].clone(),
+            many => format!("{} outputs", many.len()),
+        };
+        egui::ComboBox::from_label("")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                for (index, name) in self.parrot_names.iter().enumerate().skip(1) {
+                    let output = &clock.outputs[index];
+                    let mut on = output.enabled.load(Ordering::SeqCst);
+                    if ui.checkbox(&mut on, name.clone()).changed() {
+                        output.enabled.store(on, Ordering::SeqCst);
+                        clock.poke();
+                    }
+                }
+            });
+    }
+
+    // ticked means the port is getting clock
+    fn mute_ui(&mut self, ui: &mut egui::Ui) {
+        let clock = &self.tabs[self.tab].clock;
+        ui.horizontal_wrapped(|ui| {
+            for (output, name) in clock.outputs.iter().zip(&self.parrot_names) {
+                if !output.enabled.load(Ordering::SeqCst) {
+                    continue;
+                }
+                let mut live = !output.muted.load(Ordering::SeqCst);
+                if ui.checkbox(&mut live, name.clone()).changed() {
+                    output.muted.store(!live, Ordering::SeqCst);
+                }
+            }
+        });
+    }
+
+    fn transport_ui(&mut self, ui: &mut egui::Ui) {
+        let clock = &self.tabs[self.tab].clock;
+        let running = clock.running.load(Ordering::SeqCst);
+        if ui.button(if running { "Stop" } else { "Start" }).clicked() {
+            clock.request(if running { Request::Stop } else { Request::Start(0) });
+        }
+        // momentary, only cuts while held (button or C key)
+        let held = ui.button("Cut").is_pointer_button_down_on()
+            || (!ui.ctx().wants_keyboard_input() && ui.ctx().input(|i| i.key_down(egui::Key::C)));
+        clock.cut.store(held, Ordering::SeqCst);
+        if ui.add_enabled(running, egui::Button::new("Slow stop")).clicked() {
+            clock.slow_stop.store(true, Ordering::SeqCst);
+        }
+        // momentary too (button or H key), freezes mid-bar and resumes where it stopped
+        let hold = ui.button("Hold").is_pointer_button_down_on()
+            || (!ui.ctx().wants_keyboard_input() && ui.ctx().input(|i| i.key_down(egui::Key::H)));
+        clock.hold.store(hold, Ordering::SeqCst);
+
+        let mut armed = clock.armed.lock().unwrap();
+        if let Some(at) = *armed {
+            ui.label(format!(
+                "Starts {} (in {})",
+                schedule::clock_time(at),
+                schedule::countdown(at)
+            ));
+            if ui.small_button("x").on_hover_text("Disarm").clicked() {
+                *armed = None;
+            }
+            ui.ctx().request_repaint_after(Duration::from_millis(250));
+        }
+    }
+
+    // a segment per beat lit up through the current one, the downbeat in its own color
+    fn bar_ui(&self, ui: &mut egui::Ui) {
+        let clock = &self.tabs[self.tab].clock;
+        let running = clock.running.load(Ordering::SeqCst);
+        let beat = clock.position.load(Ordering::SeqCst) % TICKS_PER_BAR / 24;
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(200.0, 8.0), egui::Sense::hover());
+        let gap = 4.0;
+        let width = (rect.width() - gap * (BEATS_PER_BAR - 1) as f32) / BEATS_PER_BAR as f32;
+        for i in 0..BEATS_PER_BAR {
+            let min = egui::pos2(rect.left() + i as f32 * (width + gap), rect.top());
+            let segment = egui::Rect::from_min_size(min, egui::vec2(width, rect.height()));
+            let color = match i {
+                _ if !running || i > beat => ui.visuals().extreme_bg_color,
+                0 => egui::Color32::from_rgb(230, 90, 60),
+                _ => ui.visuals().selection.bg_fill,
+            };
+            ui.painter().rect_filled(segment, 2.0, color);
+        }
+    }
+
+    // where the slaved gear's patterns roll over, counted from Start
+    fn phrase_ui(&mut self, ui: &mut egui::Ui) {
+        let clock = &self.tabs[self.tab].clock;
+        let running = clock.running.load(Ordering::SeqCst);
+        let position = if running { clock.position.load(Ordering::SeqCst) } else { 0 };
+        let len = self.phrase_bars;
+        let bar = position / TICKS_PER_BAR;
+        let beat = position % TICKS_PER_BAR / 24;
+        let into = (bar % len) * BEATS_PER_BAR + beat;
+        let left = len * BEATS_PER_BAR - into;
+        ui.horizontal(|ui| {
+            ui.label(format!("Phrase {}", bar / len + 1));
+            let progress = egui::ProgressBar::new(into as f32 / (len * BEATS_PER_BAR) as f32)
+                .desired_width(140.0)
+                .text(format!("bar {} of {}", bar % len + 1, len));
+            ui.add(progress);
+            let count = |n: u64, what: &str| format!("{} {}{}", n, what, if n == 1 { "" } else { "s" });
+            let next = match (left / BEATS_PER_BAR, left % BEATS_PER_BAR) {
+                (0, beats) => count(beats, "beat"),
+                (bars, 0) => count(bars, "bar"),
+                (bars, beats) => format!("{} {}", count(bars, "bar"), count(beats, "beat")),
+            };
+            ui.label(format!("next in {}", next));
+            for bars in [16, 32] {
+                ui.selectable_value(&mut self.phrase_bars, bars, bars.to_string());
+            }
+        });
+    }
+
+    // for long unattended runs, the count only grows by what actually went out
+    fn status_ui(&self, ui: &mut egui::Ui) {
+        let clock = &self.tabs[self.tab].clock;
+        let ticks = clock.stats.ticks.load(Ordering::SeqCst);
+        let up = clock.uptime().as_secs();
+        let mut text = format!(
+            "{} ticks sent ({} beats), up {}:{:02}:{:02}",
+            ticks,
+            ticks / 24,
+            up / 3600,
+            up % 3600 / 60,
+            up % 60,
+        );
+        if clock.running.load(Ordering::SeqCst) {
+            text += &format!(", {} since Start", clock.position.load(Ordering::SeqCst));
+        }
+        ui.small(text);
+        // stopped clocks don't wake us, the uptime still moves
+        ui.ctx().request_repaint_after(Duration::from_secs(1));
+    }
+
+    // two tempos for alternating sections, switched straight or with a glide
+    fn slots_ui(&mut self, ui: &mut egui::Ui) {
+        let (min, max) = (self.bpm_min, self.bpm_max);
+        let tab = &mut self.tabs[self.tab];
+        let mut switch = None;
+        ui.horizontal(|ui| {
+            for (index, name) in ["A", "B"].into_iter().enumerate() {
+                if ui.selectable_label(tab.slot == index, name).clicked() {
+                    switch = Some(index);
+                }
+                ui.add(egui::DragValue::new(&mut tab.slots[index]).range(min..=max));
+            }
+            if ui.button("Swap").clicked() {
+                switch = Some(1 - tab.slot);
+            }
+            ui.add(egui::DragValue::new(&mut tab.fade).range(0..=64).prefix("fade ").suffix(" beats"));
+        });
+        if let Some(index) = switch {
+            tab.slot = index;
+            tab.clock.request(Request::Glide(tab.slots[index], tab.fade));
+        }
+    }
+
+    // coarse tempo changes with the mouse, in whole BPM
+    fn sweep_ui(&mut self, ui: &mut egui::Ui) {
+        let (min, max) = (self.bpm_min, self.bpm_max);
+        let clock = &self.tabs[self.tab].clock;
+        let bpm = clock.bpm.load(Ordering::SeqCst);
+        let mut target = bpm;
+        ui.horizontal(|ui| {
+            if ui.selectable_label(self.sweep_knob, "Knob").clicked() {
+                self.sweep_knob = true;
+            }
+            if ui.selectable_label(!self.sweep_knob, "Slider").clicked() {
+                self.sweep_knob = false;
+            }
+            if !self.sweep_knob {
+                // the slider clamps, so an unset or out of range tempo only changes when moved
+                ui.spacing_mut().slider_width = ui.available_width() - 60.0;
+                let mut value = bpm.clamp(min, max);
+                if ui.add(egui::Slider::new(&mut value, min..=max)).changed() {
+                    target = value;
+                }
+            }
+        });
+        if self.sweep_knob {
+            // dragging up or right turns it up, the whole range in 300 points
+            let size = 120.0;
+            let (rect, response) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::drag());
+            if response.drag_started() {
+                self.knob_drag = Some(bpm as f64);
+            }
+            if let Some(value) = &mut self.knob_drag {
+                let delta = response.drag_delta();
+                *value = (*value + (delta.x - delta.y) as f64 * (max - min) as f64 / 300.0).clamp(min as f64, max as f64);
+                target = value.round() as i32;
+            }
+            if response.drag_stopped() {
+                self.knob_drag = None;
+            }
+            // three quarters of a turn from bottom left to bottom right
+            let center = rect.center();
+            let radius = size / 2.0 - 8.0;
+            let at = |bpm: i32, radius: f32| {
+                let turned = (bpm - min) as f32 / (max - min) as f32;
+                let angle = (135.0 + 270.0 * turned).to_radians();
+                center + egui::vec2(angle.cos(), angle.sin()) * radius
+            };
+            let painter = ui.painter();
+            let lit = ui.visuals().selection.bg_fill;
+            painter.circle_filled(center, radius, ui.visuals().extreme_bg_color);
+            for mark in (min..=max).filter(|b| b % 20 == 0) {
+                painter.line_segment([at(mark, radius), at(mark, radius + 6.0)], egui::Stroke::new(1.0, lit));
+            }
+            painter.line_segment([center, at(target.clamp(min, max), radius)], egui::Stroke::new(4.0, lit));
+        }
+        if target != bpm {
+            clock.request(Request::Tempo(target));
+        }
+    }
+
+    fn set_kiosk(&mut self, on: bool) {
+        self.kiosk = on;
+        self.kiosk_exit = None;
+        self.ctx.send_viewport_cmd(egui::ViewportCommand::Decorations(!on));
+        self.ctx.send_viewport_cmd(egui::ViewportCommand::Fullscreen(on));
+    }
+
+    // tempo, tap and transport as big touch targets. closing is refused, leaving
+    // takes holding the exit button
+    fn kiosk_ui(&mut self, ctx: &egui::Context) {
+        if ctx.input(|i| i.viewport().close_requested()) {
+            ctx.send_viewport_cmd(egui::ViewportCommand::CancelClose);
+        }
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        let running = clock.running.load(Ordering::SeqCst);
+        let mut nudge = 0;
+        let mut tap = false;
+        let mut leave = false;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let big = |text: &str| egui::Button::new(egui::RichText::new(text).size(32.0));
+            ui.vertical_centered(|ui| {
+                ui.label(self.tabs[self.tab].name.as_str());
+                let value = match clock.tempo() {
+                    t if running && t > 0.0 => t.round() as i32,
+                    _ => clock.bpm.load(Ordering::SeqCst),
+                };
+                let label = egui::Label::new(egui::RichText::new(value.to_string()).font(self.impact_font.clone()))
+                    .sense(egui::Sense::click());
+                if ui.add(label).clicked() {
+                    self.keypad.show();
+                }
+                ui.columns(4, |columns| {
+                    for (column, by) in columns.iter_mut().zip([-10, -1, 1, 10]) {
+                        let text = if by > 0 { format!("+{}", by) } else { by.to_string() };
+                        if column.add_sized([column.available_width(), 80.0], big(&text)).clicked() {
+                            nudge = by;
+                        }
+                    }
+                });
+                ui.columns(2, |columns| {
+                    tap = columns[0].add_sized([columns[0].available_width(), 120.0], big("Tap")).clicked();
+                    let transport = if running { "Stop" } else { "Start" };
+                    if columns[1].add_sized([columns[1].available_width(), 120.0], big(transport)).clicked() {
+                        clock.request(if running { Request::Stop } else { Request::Start(0) });
+                    }
+                });
+                ui.add_space(20.0);
+                let exit = ui.button("Hold 2 s to leave kiosk mode");
+                if exit.is_pointer_button_down_on() {
+                    let since = *self.kiosk_exit.get_or_insert_with(Instant::now);
+                    leave = since.elapsed() >= Duration::from_secs(2);
+                    ui.ctx().request_repaint_after(Duration::from_millis(100));
+                } else {
+                    self.kiosk_exit = None;
+                }
+            });
+        });
+        if nudge != 0 {
+            let bpm = (clock.bpm.load(Ordering::SeqCst) + nudge).clamp(self.bpm_min, self.bpm_max);
+            clock.request(Request::Tempo(bpm));
+        }
+        if tap {
+            self.tap();
+        }
+        if leave {
+            self.set_kiosk(false);
+        }
+    }
+
+    fn tap(&mut self) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        if let Some(interval_secs) = self.tabs[self.tab].tapper.tap(Instant::now(), self.tap_timeout) {
+            let bpm = self.tap_rounding.apply(self.tap_note.bpm(interval_secs));
+            if bpm < self.bpm_min as f64 || bpm > self.bpm_max as f64 {
+                return;
+            }
+            if !self.tap_preview {
+                clock.request(tempo_request(bpm));
+                return;
+            }
+            let tab = &mut self.tabs[self.tab];
+            let held = match tab.tapped {
+                // unrounded taps never repeat exactly
+                Some((previous, held)) if (previous - bpm).abs() < 0.1 => held + 1,
+                _ => 1,
+            };
+            tab.tapped = Some((bpm, held));
+            if held >= tap::STABLE {
+                self.use_tapped();
+            }
+        }
+    }
+
+    fn use_tapped(&mut self) {
+        let tab = &mut self.tabs[self.tab];
+        if let Some((bpm, _)) = tab.tapped.take() {
+            tab.clock.request(tempo_request(bpm));
+        }
+    }
+
+    fn snapshot(&self) -> Snapshot {
+        Snapshot {
+            clocks: self.tabs.iter().map(|t| ClockState::capture(&t.clock)).collect(),
+            bpm_range: (self.bpm_min, self.bpm_max),
+            tap_note: self.tap_note,
+            profiles: self.profiles.clone(),
+        }
+    }
+
+    // tabs opened or closed since are left alone
+    fn restore(&mut self, snapshot: Snapshot) {
+        for (tab, state) in self.tabs.iter().zip(&snapshot.clocks) {
+            state.apply(&tab.clock);
+        }
+        (self.bpm_min, self.bpm_max) = snapshot.bpm_range;
+        self.tap_note = snapshot.tap_note;
+        if snapshot.profiles != self.profiles {
+            self.profiles = snapshot.profiles;
+            for tab in &self.tabs {
+                for (output, profile) in tab.clock.outputs.iter().zip(&self.profiles) {
+                    profile.apply(output);
+                }
+            }
+            self.save_profiles();
+        }
+    }
+
+    fn undo(&mut self) {
+        if let Some(snapshot) = self.history.undo() {
+            self.restore(snapshot);
+        }
+    }
+
+    fn redo(&mut self) {
+        if let Some(snapshot) = self.history.redo() {
+            self.restore(snapshot);
+        }
+    }
+
+    fn run_action(&mut self, action: Action) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        match action {
+            Action::Tap => self.tap(),
+            Action::Start => clock.request(Request::Start(0)),
+            Action::Stop => clock.request(Request::Stop),
+            Action::NudgeUp | Action::NudgeDown => {
+                let delta = if action == Action::NudgeUp { 1 } else { -1 };
+                let bpm = clock.bpm.load(Ordering::SeqCst) + delta;
+                clock.request(Request::Tempo(bpm.clamp(self.bpm_min, self.bpm_max)));
+            }
+            Action::NextTab => self.tab = (self.tab + 1) % self.tabs.len(),
+            Action::PrevTab => self.tab = (self.tab + self.tabs.len() - 1) % self.tabs.len(),
+        }
+    }
+
+    fn learn_ui(&mut self, ctx: &egui::Context) {
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("learn"),
+            egui::ViewportBuilder::default()
+                .with_title("MIDI Learn")
+                .with_inner_size([360.0, 260.0]),
+            |ctx, _| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    match self.in_index {
+                        Some(i) => ui.label(format!("Listening on {}", self.in_names[i])),
+                        None => ui.label("Pick an input in the monitor first"),
+                    };
+                    ui.separator();
+
+                    let mut learn = self.learn.lock().unwrap();
+                    egui::Grid::new("learn").num_columns(3).striped(true).show(ui, |ui| {
+                        for action in Action::ALL {
+                            ui.label(action.name());
+                            let triggers = learn.triggers(action);
+                            if learn.learning == Some(action) {
+                                ui.label("Send a note, CC or PC...");
+                            } else if triggers.is_empty() {
+                                ui.label("-");
+                            } else {
+                                let names: Vec<String> = triggers.iter().map(|t| t.name()).collect();
+                                ui.label(names.join(", "));
+                            }
+                            ui.horizontal(|ui| {
+                                if ui.small_button("Learn").clicked() {
+                                    learn.learning = Some(action);
+                                }
+                                if ui.small_button("Clear").clicked() {
+                                    learn.clear(action);
+                                }
+                            });
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.learn.lock().unwrap().learning = None;
+                    self.show_learn = false;
+                }
+            },
+        );
+    }
+
+    fn connect_input(&mut self, index: usize) {
+        self.in_conn = None; // close any existing connection
+        let mut midiin = match MidiInput::new("Rust Midi Input Monitor") {
+            Ok(m) => m,
+            Err(e) => {
+                eprintln!("Failed to create MIDI input: {}", e);
+                return;
+            }
+        };
+        // we want clock and sysex too
+        midiin.ignore(Ignore::None);
+        let monitor = Arc::clone(&self.monitor);
+        let pll = Arc::clone(&self.pll);
+        let learn = Arc::clone(&self.learn);
+        let ctx = self.ctx.clone();
+        let conn = midiin.connect(
+            &self.in_ports[index],
+            "midir-monitor",
+            move |_, msg, _| {
+                if msg.first() == Some(&0xF8) {
+                    pll.lock().unwrap().tick(Instant::now());
+                }
+                monitor.lock().unwrap().push(msg);
+                if learn.lock().unwrap().handle(msg) {
+                    ctx.request_repaint();
+                } else {
+                    // the monitor shows it, at most ten frames a second for a busy port
+                    ctx.request_repaint_after(Duration::from_millis(100));
+                }
+            },
+            (),
+        );
+        match conn {
+            Ok(c) => {
+                self.in_conn = Some(c);
+                self.in_index = Some(index);
+                self.monitor.lock().unwrap().reset();
+            }
+            Err(e) => {
+                eprintln!("Failed to connect to input {}: {}", index + 1, e);
+                session::record("", session::Kind::Error, format!("Failed to connect to input {}: {}", index + 1, e));
+                self.in_index = None;
+            }
+        }
+    }
+
+    fn source_ui(&mut self, ui: &mut egui::Ui) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        let source = Source::load(&clock.source);
+        let selected = match source {
+            Source::Internal => "Internal",
+            Source::ExternalMidi => "Ext MIDI",
+            Source::Ltc => "LTC in",
+            Source::Network => "Network",
+        };
+        let mut pick = None;
+        let mut set = None;
+        egui::ComboBox::from_id_salt("source")
+            .selected_text(selected)
+            .show_ui(ui, |ui| {
+                if ui.selectable_label(source == Source::Internal, "Internal").clicked() {
+                    set = Some(Source::Internal);
+                }
+                // external MIDI follows the clock on the chosen input port
+                for (index, name) in self.in_names.iter().enumerate() {
+                    let current = source == Source::ExternalMidi && self.in_index == Some(index);
+                    if ui.selectable_label(current, format!("MIDI: {}", name)).clicked() {
+                        pick = Some(index);
+                    }
+                }
+                // timecode on the default audio input
+                if ui.selectable_label(source == Source::Ltc, "LTC (audio in)").clicked() {
+                    set = Some(Source::Ltc);
+                }
+                // another instance's master tab
+                if ui.selectable_label(source == Source::Network, "Network").clicked() {
+                    set = Some(Source::Network);
+                }
+            });
+        if let Some(source) = set {
+            source.store(&clock.source);
+            clock.poke();
+        }
+        if let Some(index) = pick {
+            self.follow_input(index);
+        }
+    }
+
+    fn follow_input(&mut self, index: usize) {
+        if self.in_index != Some(index) {
+            self.connect_input(index);
+        }
+        if self.in_index.is_some() {
+            let clock = &self.tabs[self.tab].clock;
+            Source::ExternalMidi.store(&clock.source);
+            clock.poke();
+        }
+    }
+
+    // everything the palette can do right now
+    fn commands(&self) -> Vec<(String, Command)> {
+        let mut commands: Vec<(String, Command)> = Action::ALL
+            .into_iter()
+            .map(|a| (a.name().to_string(), Command::Action(a)))
+            .collect();
+        let clock = &self.tabs[self.tab].clock;
+        for (index, name) in self.parrot_names.iter().enumerate().skip(1) {
+            let verb = if clock.outputs[index].enabled.load(Ordering::SeqCst) { "Disable" } else { "Enable" };
+            commands.push((format!("{} output {}", verb, name), Command::ToggleOutput(index)));
+        }
+        commands.push(("Source: Internal".to_string(), Command::Source(None)));
+        commands.push(("Source: LTC audio in".to_string(), Command::ChaseLtc));
+        commands.push(("Source: Network".to_string(), Command::FollowNetwork));
+        for (index, name) in self.in_names.iter().enumerate() {
+            commands.push((format!("Source: MIDI {}", name), Command::Source(Some(index))));
+        }
+        for (name, window) in [
+            ("Monitor", Window::Monitor),
+            ("Automation", Window::Automation),
+            ("Cue list", Window::Cues),
+            ("Scheduler", Window::Scheduler),
+            ("Conductor", Window::Conductor),
+            ("Track library", Window::Library),
+            ("MIDI learn", Window::Learn),
+            ("Settings", Window::Settings),
+        ] {
+            commands.push((format!("Show {}", name), Command::Show(window)));
+        }
+        commands.push(("Export session log JSON".to_string(), Command::Export(true)));
+        commands.push(("Export session log CSV".to_string(), Command::Export(false)));
+        commands.push(("Undo".to_string(), Command::Undo));
+        commands.push(("Redo".to_string(), Command::Redo));
+        commands.push(("New tab".to_string(), Command::NewTab));
+        if self.tabs.len() > 1 {
+            commands.push(("Close tab".to_string(), Command::CloseTab));
+        }
+        commands
+    }
+
+    fn run_command(&mut self, command: Command) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        match command {
+            Command::Action(action) => self.run_action(action),
+            Command::SetBpm(bpm) => {
+                if bpm >= self.bpm_min && bpm <= self.bpm_max {
+                    clock.request(Request::Tempo(bpm));
+                }
+            }
+            Command::ToggleOutput(index) => {
+                let output = &clock.outputs[index];
+                output.enabled.store(!output.enabled.load(Ordering::SeqCst), Ordering::SeqCst);
+                clock.poke();
+            }
+            Command::Source(None) => {
+                Source::Internal.store(&clock.source);
+                clock.poke();
+            }
+            Command::Source(Some(index)) => self.follow_input(index),
+            Command::ChaseLtc => {
+                Source::Ltc.store(&clock.source);
+                clock.poke();
+            }
+            Command::FollowNetwork => {
+                Source::Network.store(&clock.source);
+                clock.poke();
+            }
+            Command::Show(window) => {
+                let show = match window {
+                    Window::Monitor => &mut self.show_monitor,
+                    Window::Automation => &mut self.show_automation,
+                    Window::Cues => &mut self.show_cues,
+                    Window::Scheduler => &mut self.show_scheduler,
+                    Window::Conductor => &mut self.show_conductor,
+                    Window::Library => &mut self.show_library,
+                    Window::Learn => &mut self.show_learn,
+                    Window::Settings => &mut self.show_settings,
+                };
+                *show = true;
+            }
+            Command::NewTab => self.add_tab(),
+            Command::CloseTab => self.close_tab(self.tab),
+            Command::Export(json) => self.export_session(json),
+            Command::Undo => self.undo(),
+            Command::Redo => self.redo(),
+        }
+    }
+
+    // the stream only runs while some tab times itself from it
+    fn update_audio(&mut self) {
+        let wanted = self.tabs.iter().any(|t| t.clock.audio.load(Ordering::SeqCst))
+            || self.ltc.lock().unwrap().source.is_some();
+        if wanted && self.audio_stream.is_none() {
+            match audio::start_output(Arc::clone(&self.audio_clock), Arc::clone(&self.ltc)) {
+                Ok(stream) => {
+                    self.audio_stream = Some(stream);
+                    self.audio_error = None;
+                }
+                Err(e) => {
+                    eprintln!("Failed to start audio output: {}", e);
+                    session::record("", session::Kind::Error, format!("Failed to start audio output: {}", e));
+                    self.audio_error = Some(e);
+                    for tab in &self.tabs {
+                        tab.clock.audio.store(false, Ordering::SeqCst);
+                        tab.clock.poke();
+                    }
+                    self.ltc.lock().unwrap().source = None;
+                }
+            }
+        } else if !wanted && self.audio_stream.is_some() {
+            self.audio_stream = None;
+            self.audio_clock.stop();
+        }
+
+        let listening = self.tabs.iter().any(|t| Source::load(&t.clock.source) == Source::Ltc)
+            || self.beats.lock().unwrap().enabled;
+        if listening && self.input_stream.is_none() {
+            match audio::start_input(Arc::clone(&self.chase), Arc::clone(&self.beats)) {
+                Ok(stream) => {
+                    self.input_stream = Some(stream);
+                    self.audio_error = None;
+                }
+                Err(e) => {
+                    eprintln!("Failed to start audio input: {}", e);
+                    session::record("", session::Kind::Error, format!("Failed to start audio input: {}", e));
+                    self.audio_error = Some(e);
+                    for tab in &self.tabs {
+                        if Source::load(&tab.clock.source) == Source::Ltc {
+                            Source::Internal.store(&tab.clock.source);
+                            tab.clock.poke();
+                        }
+                    }
+                    self.beats.lock().unwrap().enabled = false;
+                }
+            }
+        } else if !listening && self.input_stream.is_some() {
+            self.input_stream = None;
+        }
+    }
+
+    // the socket is open while any tab follows the network
+    fn update_network(&mut self) {
+        let following = self.tabs.iter().any(|t| Source::load(&t.clock.source) == Source::Network);
+        if following && self.net_listener.is_none() && self.net_error.is_none() {
+            match net::listen(Arc::clone(&self.follower)) {
+                Ok(listener) => self.net_listener = Some(listener),
+                Err(e) => {
+                    eprintln!("{}", e);
+                    session::record("", session::Kind::Error, e.clone());
+                    self.net_error = Some(e);
+                }
+            }
+        } else if !following {
+            self.net_listener = None;
+            self.net_error = None;
+        }
+    }
+
+    // a BPM a second toward the detected tempo, the tab's own tempo stays whole
+    fn steer(&mut self) {
+        let beats = self.beats.lock().unwrap();
+        let target = match beats.bpm {
+            Some(bpm) if beats.enabled && beats.steer => bpm.round() as i32,
+            _ => return,
+        };
+        drop(beats);
+        self.ctx.request_repaint_after(Duration::from_secs(1));
+        let clock = &self.tabs[self.tab].clock;
+        if Source::load(&clock.source) != Source::Internal || self.steered.elapsed() < Duration::from_secs(1) {
+            return;
+        }
+        let target = target.clamp(self.bpm_min, self.bpm_max);
+        let bpm = clock.bpm.load(Ordering::SeqCst);
+        if bpm != target {
+            clock.request(Request::Tempo(bpm + (target - bpm).signum()));
+            self.steered = Instant::now();
+        }
+    }
+
+    fn settings_ui(&mut self, ctx: &egui::Context) {
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("settings"),
+            egui::ViewportBuilder::default()
+                .with_title("Settings")
+                .with_inner_size([320.0, 240.0]),
+            |ctx, _| {
+                let tab = &self.tabs[self.tab];
+                let offset = &tab.clock.offset;
+                let mut export = None;
+                let mut serve = None;
+                let mut relisten = false;
+                let mut profiled = false;
+                let mut kiosk = false;
+                let mut settings = None;
+                let mut marked = false;
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.heading(tab.name.as_str());
+                    if let Some(dir) = config::portable() {
+                        ui.label(format!("Portable: settings and logs are kept in {}", dir.display()));
+                    }
+                    egui::Grid::new("settings").num_columns(2).show(ui, |ui| {
+                        // applies to every tab, taps and typed tempos outside it are ignored
+                        ui.label("BPM range");
+                        ui.horizontal(|ui| {
+                            ui.add(egui::DragValue::new(&mut self.bpm_min).range(1..=self.bpm_max - 1));
+                            ui.label("to");
+                            ui.add(egui::DragValue::new(&mut self.bpm_max).range(self.bpm_min + 1..=999));
+                        });
+                        ui.end_row();
+
+                        ui.label("Touchscreen");
+                        if ui.button("Kiosk mode").on_hover_text("Also with --kiosk").clicked() {
+                            kiosk = true;
+                        }
+                        ui.end_row();
+
+                        ui.label("Power");
+                        ui.checkbox(&mut self.keep_awake, "Keep the computer awake while running");
+                        ui.end_row();
+
+                        // slow tempos are easier to tap in eighths
+                        ui.label("Tapping");
+                        egui::ComboBox::from_id_salt("tap_note")
+                            .selected_text(self.tap_note.name())
+                            .show_ui(ui, |ui| {
+                                for note in tap::Subdivision::ALL {
+                                    ui.selectable_value(&mut self.tap_note, note, note.name());
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("");
+                        ui.checkbox(&mut self.tap_preview, "Preview taps, use on Enter or once steady");
+                        ui.end_row();
+
+                        ui.label("Tap rounding");
+                        egui::ComboBox::from_id_salt("tap_rounding")
+                            .selected_text(self.tap_rounding.name())
+                            .show_ui(ui, |ui| {
+                                for rounding in tap::Rounding::ALL {
+                                    ui.selectable_value(&mut self.tap_rounding, rounding, rounding.name());
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Tap timeout");
+                        ui.add(egui::DragValue::new(&mut self.tap_timeout).range(0.5..=10.0).speed(0.1).suffix(" s"));
+                        ui.end_row();
+
+                        ui.label("Sync offset");
+                        ui.horizontal(|ui| {
+                            let mut value = offset.value.load(Ordering::SeqCst);
+                            let mut ticks = offset.ticks.load(Ordering::SeqCst);
+                            let changed = ui.radio_value(&mut ticks, false, "ms").clicked()
+                                | ui.radio_value(&mut ticks, true, "ticks").clicked();
+                            let limit = if ticks { 96 } else { 1000 };
+                            let drag = ui.add(egui::DragValue::new(&mut value).range(-limit..=limit));
+                            if changed || drag.changed() {
+                                offset.ticks.store(ticks, Ordering::SeqCst);
+                                offset.value.store(value.clamp(-limit, limit), Ordering::SeqCst);
+                                tab.clock.poke();
+                            }
+                        });
+                        ui.end_row();
+
+                        // see `midiclock --bench` for which one suits this machine
+                        ui.label("Timing");
+                        let sleep = Sleep::load(&tab.clock.sleep);
+                        egui::ComboBox::from_id_salt("sleep")
+                            .selected_text(sleep.name())
+                            .show_ui(ui, |ui| {
+                                for (index, option) in Sleep::ALL.into_iter().enumerate() {
+                                    if ui.selectable_label(sleep == option, option.name()).clicked() {
+                                        tab.clock.sleep.store(index, Ordering::SeqCst);
+                                        tab.clock.poke();
+                                    }
+                                }
+                            });
+                        ui.end_row();
+
+                        ui.label("Tempo slew");
+                        ui.horizontal(|ui| {
+                            let was = tab.clock.slew.load(Ordering::SeqCst);
+                            let mut rate = was;
+                            let mut on = rate > 0;
+                            if ui.checkbox(&mut on, "").changed() {
+                                rate = if on { 10 } else { 0 };
+                            }
+                            ui.add_enabled(on, egui::DragValue::new(&mut rate).range(1..=200).suffix(" BPM/s"));
+                            let rate = if on { rate } else { 0 };
+                            if rate != was {
+                                tab.clock.slew.store(rate, Ordering::SeqCst);
+                                tab.clock.poke();
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("Slow stop");
+                        let mut beats = tab.clock.slow_stop_beats.load(Ordering::SeqCst);
+                        let drag = egui::DragValue::new(&mut beats).range(1..=64).suffix(" beats");
+                        if ui.add(drag).changed() {
+                            tab.clock.slow_stop_beats.store(beats, Ordering::SeqCst);
+                            tab.clock.poke();
+                        }
+                        ui.end_row();
+
+                        ui.label("Hold");
+                        let mut stop = tab.clock.hold_stop.load(Ordering::SeqCst);
+                        if ui.checkbox(&mut stop, "Send Stop and Continue").changed() {
+                            tab.clock.hold_stop.store(stop, Ordering::SeqCst);
+                            tab.clock.poke();
+                        }
+                        ui.end_row();
+
+                        // for timed exercises and fixed-length hardware renders
+                        ui.label("Stop after");
+                        ui.horizontal(|ui| {
+                            let was = tab.clock.stop_after.load(Ordering::SeqCst);
+                            let mut bars = was;
+                            let mut on = bars > 0;
+                            if ui.checkbox(&mut on, "").changed() {
+                                bars = if on { 8 } else { 0 };
+                            }
+                            ui.add_enabled(on, egui::DragValue::new(&mut bars).range(1..=9999).suffix(" bars"));
+                            let bars = if on { bars } else { 0 };
+                            if bars != was {
+                                tab.clock.stop_after.store(bars, Ordering::SeqCst);
+                                tab.clock.poke();
+                            }
+                        });
+                        ui.end_row();
+
+                        ui.label("");
+                        let mut on = tab.clock.audio.load(Ordering::SeqCst);
+                        if ui.checkbox(&mut on, "Audio callback timing").changed() {
+                            tab.clock.audio.store(on, Ordering::SeqCst);
+                            tab.clock.poke();
+                        }
+                        ui.end_row();
+
+                        // ticked ports get 0xF9 every 10 ms instead of beat clock
+                        ui.label("MIDI Tick");
+                        ui.horizontal_wrapped(|ui| {
+                            for (output, name) in tab.clock.outputs.iter().zip(&self.parrot_names) {
+                                if !output.enabled.load(Ordering::SeqCst) {
+                                    continue;
+                                }
+                                let mut on = output.midi_tick.load(Ordering::SeqCst);
+                                if ui.checkbox(&mut on, name.as_str()).changed() {
+                                    output.midi_tick.store(on, Ordering::SeqCst);
+                                }
+                            }
+                        });
+                        ui.end_row();
+                    });
+                    if let Some(e) = &self.audio_error {
+                        ui.colored_label(egui::Color32::RED, e);
+                    }
+
+                    ui.collapsing("Device profiles", |ui| {
+                        ui.label("Quirks of the device on each port, for every tab.");
+                        egui::Grid::new("profiles").num_columns(4).show(ui, |ui| {
+                            for (index, name) in self.parrot_names.iter().enumerate().skip(1) {
+                                let profile = &mut self.profiles[index];
+                                let before = profile.clone();
+                                ui.label(name.as_str());
+                                egui::ComboBox::from_id_salt(("ppqn", index))
+                                    .selected_text(format!("{} ppqn", profile.ppqn))
+                                    .show_ui(ui, |ui| {
+                                        for ppqn in profile::PPQN {
+                                            ui.selectable_value(&mut profile.ppqn, ppqn, format!("{} ppqn", ppqn));
+                                        }
+                                    });
+                                ui.checkbox(&mut profile.gate, "Only while running")
+                                    .on_hover_text("No clock while stopped, for devices that start on the first one");
+                                ui.add(egui::DragValue::new(&mut profile.preroll).range(0..=8).prefix("pre-roll ").suffix(" beats"));
+                                ui.end_row();
+                                ui.label("");
+                                ui.add(egui::TextEdit::singleline(&mut profile.sysex).hint_text("startup SysEx, hex or .syx file"));
+                                if let Err(e) = profile::sysex(&profile.sysex) {
+                                    ui.colored_label(egui::Color32::RED, e);
+                                }
+                                ui.end_row();
+                                ui.label("");
+                                ui.add(egui::TextEdit::singleline(&mut profile.tempo_sysex).hint_text("tempo SysEx, e.g. F0 43 10 {bpm10:2} F7"));
+                                if let Err(e) = profile::Template::parse(&profile.tempo_sysex) {
+                                    ui.colored_label(egui::Color32::RED, e);
+                                }
+                                ui.end_row();
+                                profiled |= *profile != before;
+                            }
+                        });
+                    });
+
+                    ui.collapsing("Test mode", |ui| {
+                        ui.label("Degrades the output on purpose, to see how slaves cope.");
+                        let humanize = &tab.clock.humanize;
+                        let mut jitter = humanize.jitter_us.load(Ordering::SeqCst) as f64 / 1000.0;
+                        let drag = egui::DragValue::new(&mut jitter).range(0.0..=20.0).speed(0.05).suffix(" ms");
+                        if ui.horizontal(|ui| { ui.label("Jitter"); ui.add(drag) }).inner.changed() {
+                            humanize.jitter_us.store((jitter * 1000.0) as u32, Ordering::SeqCst);
+                            tab.clock.poke();
+                        }
+                        let mut wander = humanize.wander_ppm.load(Ordering::SeqCst);
+                        let drag = egui::DragValue::new(&mut wander).range(0..=50000).speed(10.0).suffix(" ppm");
+                        if ui.horizontal(|ui| { ui.label("Wander"); ui.add(drag) }).inner.changed() {
+                            humanize.wander_ppm.store(wander, Ordering::SeqCst);
+                            tab.clock.poke();
+                        }
+                        // per minute of running, so a take shows how far behind a slave falls
+                        let ppm = humanize.drift_ppm.load(Ordering::SeqCst);
+                        ui.horizontal(|ui| {
+                            ui.label("Drift");
+                            let changed = if self.drift_cents {
+                                let mut cents = testmode::ppm_to_cents(ppm as f64);
+                                let drag = egui::DragValue::new(&mut cents).range(-50.0..=50.0).speed(0.01).suffix(" cents/min");
+                                let changed = ui.add(drag).changed();
+                                changed.then(|| testmode::cents_to_ppm(cents).round() as i32)
+                            } else {
+                                let mut ppm = ppm;
+                                let drag = egui::DragValue::new(&mut ppm).range(-30000..=30000).speed(1.0).suffix(" ppm/min");
+                                ui.add(drag).changed().then_some(ppm)
+                            };
+                            if let Some(ppm) = changed {
+                                humanize.drift_ppm.store(ppm, Ordering::SeqCst);
+                                tab.clock.poke();
+                            }
+                            ui.radio_value(&mut self.drift_cents, false, "ppm");
+                            ui.radio_value(&mut self.drift_cents, true, "cents");
+                        });
+                        if ppm != 0 {
+                            let drifted = humanize.drifted_ppm();
+                            ui.horizontal(|ui| {
+                                ui.label(format!("Drifted {:+.0} ppm, {:+.2} cents", drifted, testmode::ppm_to_cents(drifted)));
+                                if ui.small_button("Reset").clicked() {
+                                    humanize.drift_reset.store(true, Ordering::SeqCst);
+                                }
+                            });
+                            ui.ctx().request_repaint_after(Duration::from_secs(1));
+                        }
+                    });
+
+                    ui.collapsing("LTC output", |ui| {
+                        ui.label("SMPTE timecode on the default audio output, following the transport.");
+                        let mut ltc = self.ltc.lock().unwrap();
+                        let mut on = ltc.source.as_ref().is_some_and(|c| Arc::ptr_eq(c, &tab.clock));
+                        if ui.checkbox(&mut on, "Send LTC for this clock").changed() {
+                            ltc.source = on.then(|| Arc::clone(&tab.clock));
+                        }
+                        egui::Grid::new("ltc").num_columns(2).show(ui, |ui| {
+                            ui.label("Frame rate");
+                            egui::ComboBox::from_id_salt("ltc_fps")
+                                .selected_text(ltc.fps.name())
+                                .show_ui(ui, |ui| {
+                                    for fps in ltc::Fps::ALL {
+                                        ui.selectable_value(&mut ltc.fps, fps, fps.name());
+                                    }
+                                });
+                            ui.end_row();
+                            ui.label("Channel");
+                            let mut channel = ltc.channel + 1;
+                            if ui.add(egui::DragValue::new(&mut channel).range(1..=32)).changed() {
+                                ltc.channel = channel - 1;
+                            }
+                            ui.end_row();
+                            ui.label("Start hour");
+                            ui.add(egui::DragValue::new(&mut ltc.start_hour).range(0..=23));
+                            ui.end_row();
+                        });
+                    });
+
+                    ui.collapsing("Beat detection", |ui| {
+                        ui.label("Listens to the default audio input for the band's tempo.");
+                        let mut beats = self.beats.lock().unwrap();
+                        ui.checkbox(&mut beats.enabled, "Listen");
+                        ui.add_enabled(beats.enabled, egui::Checkbox::new(&mut beats.steer, "Steer this tab's tempo toward it"));
+                        match beats.bpm {
+                            Some(bpm) if beats.enabled => {
+                                ui.label(format!("Hearing {:.1} BPM ({:.0}% sure)", bpm, beats.confidence * 100.0))
+                            }
+                            _ if beats.enabled => ui.label("Listening..."),
+                            _ => ui.label("-"),
+                        };
+                        if beats.enabled {
+                            ctx.request_repaint_after(Duration::from_millis(500));
+                        }
+                    });
+
+                    ui.collapsing("LTC input", |ui| {
+                        ui.label("With the source on LTC, the transport chases timecode on the default audio input.");
+                        let mut chase = self.chase.lock().unwrap();
+                        egui::Grid::new("chase").num_columns(2).show(ui, |ui| {
+                            ui.label("Channel");
+                            let mut channel = chase.channel + 1;
+                            if ui.add(egui::DragValue::new(&mut channel).range(1..=32)).changed() {
+                                chase.channel = channel - 1;
+                            }
+                            ui.end_row();
+                            ui.label("Song starts at hour");
+                            ui.add(egui::DragValue::new(&mut chase.start_hour).range(0..=23));
+                            ui.end_row();
+                            ui.label("Receiving");
+                            match (chase.timecode, chase.fps) {
+                                (Some([h, m, s, f]), Some(fps)) => {
+                                    ui.label(format!("{:02}:{:02}:{:02}:{:02} @ {}", h, m, s, f, fps.name()))
+                                }
+                                _ => ui.label("-"),
+                            };
+                            ui.end_row();
+                        });
+                    });
+
+                    ui.collapsing("Scheduled start", |ui| {
+                        egui::Grid::new("arm").num_columns(2).show(ui, |ui| {
+                            ui.label("Start at");
+                            ui.text_edit_singleline(&mut self.arm_time);
+                            ui.end_row();
+                            ui.label("Local time");
+                            ui.label(schedule::clock_time(SystemTime::now()));
+                            ui.end_row();
+                        });
+                        ctx.request_repaint_after(Duration::from_secs(1));
+                        let parsed = schedule::parse(&self.arm_time);
+                        let mut armed = tab.clock.armed.lock().unwrap();
+                        ui.horizontal(|ui| {
+                            if ui.add_enabled(parsed.is_some(), egui::Button::new("Arm")).clicked()
+                                && let Some(secs) = parsed
+                            {
+                                *armed = Some(schedule::next(secs, SystemTime::now()));
+                                tab.clock.poke();
+                            }
+                            if ui.add_enabled(armed.is_some(), egui::Button::new("Disarm")).clicked() {
+                                *armed = None;
+                            }
+                        });
+                        match (*armed, parsed) {
+                            (Some(at), _) => ui.label(format!("Armed, in {}", schedule::countdown(at))),
+                            (None, None) => ui.colored_label(egui::Color32::RED, "Use HH:MM or HH:MM:SS"),
+                            (None, Some(_)) => ui.label("Arm each tab to start them together"),
+                        };
+                    });
+
+                    ui.collapsing("Network sync", |ui| {
+                        ui.label("Tabs on the Network source follow the tempo, transport and phase of a master tab on the LAN.");
+                        let mut master = tab.clock.net.lock().unwrap();
+                        if ui.checkbox(&mut master.enabled, "Send this tab as master").changed() {
+                            tab.clock.poke();
+                        }
+                        egui::Grid::new("net").num_columns(2).show(ui, |ui| {
+                            ui.label("Send to");
+                            ui.add(egui::TextEdit::singleline(&mut master.target).hint_text("broadcast"));
+                            ui.end_row();
+                            ui.label("Master port");
+                            ui.add(egui::DragValue::new(&mut master.port).range(1024..=65535));
+                            ui.end_row();
+                            let mut follower = self.follower.lock().unwrap();
+                            ui.label("Listen port");
+                            if ui.add(egui::DragValue::new(&mut follower.port).range(1024..=65535)).changed() {
+                                relisten = true;
+                            }
+                            ui.end_row();
+                            ui.label("Following");
+                            match follower.master {
+                                Some(from) if follower.connected() => {
+                                    let state = if follower.running { "running" } else { "stopped" };
+                                    ui.label(format!("{} at {:.1} BPM, {}", from.ip(), follower.tempo, state))
+                                }
+                                _ => ui.label("-"),
+                            };
+                            ui.end_row();
+                        });
+                        if let Some(e) = &self.net_error {
+                            ui.colored_label(egui::Color32::RED, e);
+                        }
+                    });
+
+                    ui.collapsing("Lighting", |ui| {
+                        ui.label("Beat and bar pulses as DMX channels over Art-Net or sACN.");
+                        let mut dmx = tab.clock.dmx.lock().unwrap();
+                        egui::Grid::new("dmx").num_columns(2).show(ui, |ui| {
+                            ui.label("Protocol");
+                            egui::ComboBox::from_id_salt("dmx_protocol")
+                                .selected_text(dmx.protocol.name())
+                                .show_ui(ui, |ui| {
+                                    for protocol in dmx::Protocol::ALL {
+                                        ui.selectable_value(&mut dmx.protocol, protocol, protocol.name());
+                                    }
+                                });
+                            ui.end_row();
+                            ui.label("Target IP");
+                            ui.add(egui::TextEdit::singleline(&mut dmx.target).hint_text("broadcast / multicast"));
+                            ui.end_row();
+                            ui.label("Universe");
+                            let (first, last) = dmx.protocol.universes();
+                            dmx.universe = dmx.universe.clamp(first, last);
+                            ui.add(egui::DragValue::new(&mut dmx.universe).range(first..=last));
+                            ui.end_row();
+                            ui.label("Beat channel");
+                            ui.add(egui::DragValue::new(&mut dmx.beat_channel).range(0..=512));
+                            ui.end_row();
+                            ui.label("Bar channel");
+                            ui.add(egui::DragValue::new(&mut dmx.bar_channel).range(0..=512));
+                            ui.end_row();
+                        });
+                    });
+
+                    #[cfg(feature = "gpio")]
+                    ui.collapsing("GPIO", |ui| {
+                        ui.label("Pulses a Raspberry Pi pin while running, for sync inputs without MIDI.");
+                        let mut gpio = tab.clock.gpio.lock().unwrap();
+                        egui::Grid::new("gpio").num_columns(2).show(ui, |ui| {
+                            ui.label("Pulse on");
+                            egui::ComboBox::from_id_salt("gpio_rate")
+                                .selected_text(gpio.rate.name())
+                                .show_ui(ui, |ui| {
+                                    for rate in gpio::Rate::ALL {
+                                        ui.selectable_value(&mut gpio.rate, rate, rate.name());
+                                    }
+                                });
+                            ui.end_row();
+                            ui.label("Pin (BCM)");
+                            ui.add(egui::DragValue::new(&mut gpio.pin).range(0..=27));
+                            ui.end_row();
+                            ui.label("Pulse width");
+                            ui.add(egui::DragValue::new(&mut gpio.width_ms).range(1..=20).suffix(" ms"));
+                            ui.end_row();
+                        });
+                        if let Some(e) = &gpio.error {
+                            ui.colored_label(egui::Color32::RED, e);
+                        }
+                    });
+
+                    ui.collapsing("Smart lights", |ui| {
+                        ui.label("Flashes Hue and WLED lights on the beat.");
+                        let mut smart = tab.clock.lights.lock().unwrap();
+                        ui.horizontal(|ui| {
+                            ui.label("Beat");
+                            ui.color_edit_button_srgb(&mut smart.beat);
+                            ui.label("Downbeat");
+                            ui.color_edit_button_srgb(&mut smart.downbeat);
+                        });
+                        let mut remove = None;
+                        for (index, fixture) in smart.fixtures.iter_mut().enumerate() {
+                            ui.push_id(index, |ui| {
+                                ui.separator();
+                                ui.horizontal(|ui| {
+                                    ui.checkbox(&mut fixture.enabled, "");
+                                    egui::ComboBox::from_id_salt("kind")
+                                        .selected_text(fixture.kind.name())
+                                        .show_ui(ui, |ui| {
+                                            for kind in lights::Kind::ALL {
+                                                ui.selectable_value(&mut fixture.kind, kind, kind.name());
+                                            }
+                                        });
+                                    ui.add(egui::TextEdit::singleline(&mut fixture.address).hint_text("IP address").desired_width(110.0));
+                                    if ui.small_button("x").clicked() {
+                                        remove = Some(index);
+                                    }
+                                });
+                                ui.horizontal(|ui| {
+                                    match fixture.kind {
+                                        lights::Kind::Hue => {
+                                            ui.add(egui::TextEdit::singleline(&mut fixture.user).hint_text("bridge user").desired_width(110.0));
+                                            ui.label("Light");
+                                            ui.add(egui::DragValue::new(&mut fixture.light).range(1..=999));
+                                        }
+                                        lights::Kind::Wled => {
+                                            ui.label("LEDs");
+                                            ui.add(egui::DragValue::new(&mut fixture.leds).range(1..=490));
+                                        }
+                                    }
+                                    ui.checkbox(&mut fixture.downbeat_only, "Downbeat only");
+                                });
+                            });
+                        }
+                        if let Some(index) = remove {
+                            smart.fixtures.remove(index);
+                        }
+                        if ui.button("Add light").clicked() {
+                            smart.fixtures.push(lights::Fixture::new());
+                        }
+                    });
+
+                    ui.collapsing("MQTT", |ui| {
+                        let mut mqtt = tab.clock.mqtt.lock().unwrap();
+                        ui.checkbox(&mut mqtt.enabled, "Publish tempo, beats and transport");
+                        egui::Grid::new("mqtt").num_columns(2).show(ui, |ui| {
+                            ui.label("Broker");
+                            ui.add(egui::TextEdit::singleline(&mut mqtt.broker).hint_text("host:1883"));
+                            ui.end_row();
+                            ui.label("Topic prefix");
+                            ui.text_edit_singleline(&mut mqtt.prefix);
+                            ui.end_row();
+                        });
+                        if !mqtt.status.is_empty() {
+                            ui.label(mqtt.status.as_str());
+                        }
+                    });
+
+                    ui.collapsing("Metrics", |ui| {
+                        ui.horizontal(|ui| {
+                            let mut on = self.metrics.is_some();
+                            if ui.checkbox(&mut on, "Prometheus metrics on port").changed() {
+                                serve = Some(on);
+                            }
+                            ui.add_enabled(
+                                self.metrics.is_none(),
+                                egui::DragValue::new(&mut self.metrics_port).range(1..=65535),
+                            );
+                        });
+                        if let Some(e) = &self.metrics_error {
+                            ui.colored_label(egui::Color32::RED, e);
+                        }
+                    });
+
+                    ui.collapsing("Session log", |ui| {
+                        ui.label(format!("{} events: tempo, transport, ports and errors", session::len()));
+                        ui.horizontal(|ui| {
+                            if ui.button("Export JSON").clicked() {
+                                export = Some(true);
+                            }
+                            if ui.button("Export CSV").clicked() {
+                                export = Some(false);
+                            }
+                        });
+                        if let Some(status) = &self.export_status {
+                            ui.label(status);
+                        }
+                    });
+
+                    ui.collapsing("MIDI capture", |ui| {
+                        ui.label("Records every byte sent with its time, for looking into timing problems.");
+                        let label = if capture::active() { "Stop capture" } else { "Start capture" };
+                        if ui.button(label).clicked() {
+                            self.capture_status = Some(match capture::active() {
+                                false => capture::start(self.parrot_names.clone()).map(|name| format!("Capturing to {}", name)),
+                                true => capture::stop().map(|name| format!("Saved, also as {}", name)),
+                            }.unwrap_or_else(|e| e));
+                        }
+                        if let Some(status) = &self.capture_status {
+                            ui.label(status);
+                        }
+                    });
+
+                    ui.collapsing("Tempo markings", |ui| {
+                        marked |= ui.checkbox(&mut self.markings.show, "Show the marking next to the tempo").changed();
+                        let mut remove = None;
+                        egui::Grid::new("markings").num_columns(3).show(ui, |ui| {
+                            for (index, (from, name)) in self.markings.ranges.iter_mut().enumerate() {
+                                marked |= ui.add(egui::DragValue::new(from).range(0..=999).prefix("from ")).changed();
+                                marked |= ui.text_edit_singleline(name).changed();
+                                if ui.small_button("x").clicked() {
+                                    remove = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        if let Some(index) = remove {
+                            self.markings.ranges.remove(index);
+                            marked = true;
+                        }
+                        ui.horizontal(|ui| {
+                            if ui.button("Add").clicked() {
+                                let from = self.markings.ranges.iter().map(|(from, _)| from + 1).max().unwrap_or(0);
+                                self.markings.ranges.push((from, String::new()));
+                                marked = true;
+                            }
+                            if ui.button("Defaults").clicked() {
+                                self.markings.ranges = Markings::new().ranges;
+                                marked = true;
+                            }
+                        });
+                    });
+
+                    // this tab's ports and offset, the tempo range, tapping, key bindings and
+                    // device profiles, as one file to switch between or hand to another machine
+                    ui.collapsing("Settings profiles", |ui| {
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.profile_name);
+                            let name = self.profile_name.trim();
+                            let valid = !name.is_empty() && !name.contains(['/', '\\', ':']);
+                            if ui.add_enabled(valid, egui::Button::new("Save")).clicked() {
+                                settings = Some(SettingsProfile::Save(name.to_string()));
+                            }
+                        });
+                        egui::Grid::new("settings profiles").num_columns(3).show(ui, |ui| {
+                            for name in config::profiles() {
+                                ui.label(name.as_str());
+                                if ui.button("Load").clicked() {
+                                    settings = Some(SettingsProfile::Load(name.clone()));
+                                }
+                                if ui.button("Export").on_hover_text("Where session logs go").clicked() {
+                                    settings = Some(SettingsProfile::Export(name));
+                                }
+                                ui.end_row();
+                            }
+                        });
+                        ui.label(format!("Drop a .{} file on the window to import it.", config::PROFILE_EXT));
+                        if let Some(status) = &self.profile_status {
+                            ui.label(status);
+                        }
+                    });
+                });
+                if let Some(json) = export {
+                    self.export_session(json);
+                }
+                if marked {
+                    self.config.set_all("show_marking", vec![if self.markings.show { "on" } else { "off" }.to_string()]);
+                    self.config.set_all("marking", self.markings.to_config());
+                    self.config.save();
+                }
+                match settings {
+                    Some(SettingsProfile::Save(name)) => {
+                        self.profile_status = Some(match self.settings().write(&config::profile_path(&name)) {
+                            Ok(()) => format!("Saved {}", name),
+                            Err(e) => e,
+                        });
+                    }
+                    Some(SettingsProfile::Load(name)) => self.load_settings(&config::profile_path(&name)),
+                    Some(SettingsProfile::Export(name)) => {
+                        let file = format!("midiclock-{}.{}", name, config::PROFILE_EXT);
+                        self.profile_status = Some(match fs::copy(config::profile_path(&name), config::output(&file)) {
+                            Ok(_) => format!("Saved {}", file),
+                            Err(e) => format!("Failed to write {}: {}", file, e),
+                        });
+                    }
+                    None => {}
+                }
+                if kiosk {
+                    self.set_kiosk(true);
+                    self.show_settings = false;
+                }
+                if profiled {
+                    for tab in &self.tabs {
+                        for (output, profile) in tab.clock.outputs.iter().zip(&self.profiles) {
+                            profile.apply(output);
+                        }
+                    }
+                    self.save_profiles();
+                }
+                if relisten {
+                    self.net_listener = None;
+                    self.net_error = None;
+                }
+                match serve {
+                    Some(true) => self.start_metrics(),
+                    Some(false) => self.metrics = None,
+                    None => {}
+                }
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_settings = false;
+                }
+            },
+        );
+    }
+
+    // follows the current tab's tempo onto its ports, and offers a port's old tempo
+    // when it's picked again however that happened
+    fn remember_tempos(&mut self) {
+        let tab = &mut self.tabs[self.tab];
+        let bpm = tab.clock.bpm.load(Ordering::SeqCst);
+        for (index, output) in tab.clock.outputs.iter().enumerate().skip(1) {
+            let on = output.enabled.load(Ordering::SeqCst);
+            let was = std::mem::replace(&mut tab.enabled[index], on);
+            if on && !was {
+                if let Some(last) = self.port_tempos[index].filter(|&t| t != bpm) {
+                    self.recall = Some((index, last));
+                }
+            } else if on && bpm > 0 && self.port_tempos[index] != Some(bpm) && self.recall.is_none_or(|(p, _)| p != index) {
+                self.port_tempos[index] = Some(bpm);
+                self.port_tempos_changed.get_or_insert_with(Instant::now);
+            }
+        }
+        // nudging through tempos shouldn't write the file each step
+        if let Some(changed) = self.port_tempos_changed {
+            if changed.elapsed() < Duration::from_secs(2) {
+                self.ctx.request_repaint_after(Duration::from_secs(2) - changed.elapsed());
+                return;
+            }
+            self.port_tempos_changed = None;
+            let mut saved: Vec<String> = self.config.all("port_tempo").into_iter()
+                .filter(|v| v.split_once(';').is_some_and(|(_, port)| !self.parrot_names.iter().any(|n| n == port)))
+                .map(str::to_string)
+                .collect();
+            for (name, bpm) in self.parrot_names.iter().zip(&self.port_tempos) {
+                if let Some(bpm) = bpm {
+                    saved.push(format!("{};{}", bpm, name));
+                }
+            }
+            self.config.set_all("port_tempo", saved);
+            self.config.save();
+        }
+    }
+
+    // profiles for ports that aren't plugged in now are kept for when they are
+    fn save_profiles(&mut self) {
+        let mut saved: Vec<String> = self.config.all("profile").into_iter()
+            .filter(|v| Profile::from_config(v).is_some_and(|(port, _)| !self.parrot_names.contains(&port)))
+            .map(str::to_string)
+            .collect();
+        for (name, profile) in self.parrot_names.iter().zip(&self.profiles).skip(1) {
+            if *profile != Profile::new() {
+                saved.push(profile.to_config(name));
+            }
+        }
+        self.config.set_all("profile", saved);
+        self.config.save();
+    }
+
+    // a settings profile of how things are now
+    fn settings(&self) -> Config {
+        let clock = &self.tabs[self.tab].clock;
+        let mut settings = Config::new();
+        for (output, name) in clock.outputs.iter().zip(&self.parrot_names).skip(1) {
+            if output.enabled.load(Ordering::SeqCst) {
+                settings.add("port", name.clone());
+            }
+        }
+        let (value, ticks) = (clock.offset.value.load(Ordering::SeqCst), clock.offset.ticks.load(Ordering::SeqCst));
+        settings.add("offset", format!("{};{}", value, if ticks { "ticks" } else { "ms" }));
+        settings.add("bpm", clock.bpm.load(Ordering::SeqCst).to_string());
+        settings.add("bpm_range", format!("{};{}", self.bpm_min, self.bpm_max));
+        settings.add("tap_timeout", self.tap_timeout.to_string());
+        settings.set_all("map", self.learn.lock().unwrap().save());
+        settings.set_all("profile", self.config.all("profile").into_iter().map(str::to_string).collect());
+        settings
+    }
+
+    // anything the profile leaves out stays as it is
+    fn apply_settings(&mut self, settings: &Config) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        let ports = settings.all("port");
+        if !ports.is_empty() {
+            for (output, name) in clock.outputs.iter().zip(&self.parrot_names).skip(1) {
+                output.enabled.store(ports.contains(&name.as_str()), Ordering::SeqCst);
+            }
+            clock.poke();
+        }
+        if let Some((value, unit)) = settings.first("offset").and_then(|v| v.split_once(';'))
+            && let Ok(value) = value.parse::<i32>()
+        {
+            let ticks = unit == "ticks";
+            let limit = if ticks { 96 } else { 1000 };
+            clock.offset.ticks.store(ticks, Ordering::SeqCst);
+            clock.offset.value.store(value.clamp(-limit, limit), Ordering::SeqCst);
+            clock.poke();
+        }
+        if let Some((min, max)) = settings.first("bpm_range").and_then(|v| v.split_once(';'))
+            && let (Ok(min), Ok(max)) = (min.parse::<i32>(), max.parse::<i32>())
+            && 1 <= min && min < max && max <= 999
+        {
+            (self.bpm_min, self.bpm_max) = (min, max);
+        }
+        if let Some(bpm) = settings.first("bpm").and_then(|v| v.parse::<i32>().ok()) {
+            clock.request(Request::Tempo(bpm.clamp(self.bpm_min, self.bpm_max)));
+        }
+        if let Some(timeout) = settings.first("tap_timeout").and_then(|v| v.parse::<f64>().ok()) {
+            self.tap_timeout = timeout.clamp(0.5, 10.0);
+        }
+        let map = settings.all("map");
+        if !map.is_empty() {
+            let mut learn = self.learn.lock().unwrap();
+            learn.bindings.clear();
+            learn.load(&map);
+            learn.dirty = true;
+        }
+        let saved: Vec<_> = settings.all("profile").iter().filter_map(|v| Profile::from_config(v)).collect();
+        if !saved.is_empty() {
+            for (name, profile) in self.parrot_names.iter().zip(self.profiles.iter_mut()) {
+                if let Some((_, p)) = saved.iter().find(|(port, _)| port == name) {
+                    *profile = p.clone();
+                }
+            }
+            for tab in &self.tabs {
+                for (output, profile) in tab.clock.outputs.iter().zip(&self.profiles) {
+                    profile.apply(output);
+                }
+            }
+            // the profile's word wins for ports not plugged in here either
+            let absent = |v: &&str| Profile::from_config(v).filter(|(port, _)| !self.parrot_names.contains(port));
+            let theirs: Vec<String> = settings.all("profile").iter().filter_map(absent).map(|(port, _)| port).collect();
+            let kept: Vec<String> = settings.all("profile").into_iter()
+                .filter(|v| absent(v).is_some())
+                .chain(self.config.all("profile").into_iter()
+                    .filter(|v| Profile::from_config(v).is_some_and(|(port, _)| !theirs.contains(&port))))
+                .map(str::to_string)
+                .collect();
+            self.config.set_all("profile", kept);
+            self.save_profiles();
+        }
+        self.config.save();
+    }
+
+    fn load_settings(&mut self, path: &Path) {
+        self.profile_status = Some(match Config::read(path) {
+            Ok(settings) => {
+                self.apply_settings(&settings);
+                format!("Loaded {}", file_name(path))
+            }
+            Err(e) => e,
+        });
+    }
+
+    // keeps a copy with the others, named after the file, then switches to it
+    fn import_settings(&mut self, path: PathBuf) {
+        let name = path.file_stem().map(|s| s.to_string_lossy().into_owned()).unwrap_or_default();
+        let name = name.strip_prefix("midiclock-").unwrap_or(&name).to_string();
+        let to = config::profile_path(&name);
+        if let Some(dir) = to.parent() {
+            let _ = fs::create_dir_all(dir);
+        }
+        if let Err(e) = fs::copy(&path, &to) {
+            self.profile_status = Some(format!("Failed to import {}: {}", file_name(&path), e));
+            return;
+        }
+        self.load_settings(&to);
+        self.show_settings = true;
+    }
+
+    fn start_metrics(&mut self) {
+        match metrics::serve(self.metrics_port, Arc::clone(&self.clocks), self.started) {
+            Ok(server) => {
+                self.metrics = Some(server);
+                self.metrics_error = None;
+            }
+            Err(e) => {
+                eprintln!("{}", e);
+                session::record("", session::Kind::Error, e.clone());
+                self.metrics_error = Some(e);
+            }
+        }
+    }
+
+    // offline, off the gui thread, then sets the tab to whatever was found
+    fn analyze_file(&mut self, path: PathBuf) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        let status = Arc::clone(&self.analysis);
+        let ctx = self.ctx.clone();
+        let (lo, hi) = (self.bpm_min, self.bpm_max);
+        let name = file_name(&path);
+        self.tempo_map = None;
+        *status.lock().unwrap() = Some(format!("Analysing {}...", name));
+        thread::spawn(move || {
+            let text = match wav::read(&path).and_then(|(samples, rate)| beat::analyze(&samples, rate)) {
+                Ok(bpm) if (lo..=hi).contains(&(bpm.round() as i32)) => {
+                    clock.request(Request::Tempo(bpm.round() as i32));
+                    format!("{}: {:.1} BPM", name, bpm)
+                }
+                Ok(bpm) => format!("{}: {:.1} BPM is outside the BPM range", name, bpm),
+                Err(e) => format!("{}: {}", name, e),
+            };
+            *status.lock().unwrap() = Some(text);
+            ctx.request_repaint();
+        });
+    }
+
+    // the opening tempo straight away, changes after it are offered for the automation
+    fn open_midi_file(&mut self, path: PathBuf) {
+        let name = file_name(&path);
+        let text = match smf::tempo_map(&path) {
+            Ok(map) => {
+                let bpm = map[0].1;
+                let mut text = format!("{}: {:.1} BPM", name, bpm);
+                if (self.bpm_min..=self.bpm_max).contains(&(bpm.round() as i32)) {
+                    self.tabs[self.tab].clock.request(Request::Tempo(bpm.round() as i32));
+                } else {
+                    text += " is outside the BPM range";
+                }
+                if map.len() > 1 {
+                    text += &format!(", {} tempo changes", map.len() - 1);
+                }
+                self.tempo_map = (map.len() > 1).then_some(map);
+                text
+            }
+            Err(e) => {
+                self.tempo_map = None;
+                format!("{}: {}", name, e)
+            }
+        };
+        *self.analysis.lock().unwrap() = Some(text);
+    }
+
+    // kept in the config so the list is there next time, from the file as it is then
+    fn import_library(&mut self, path: PathBuf) {
+        self.library_status = Some(match library::load(&path) {
+            Ok(tracks) => {
+                let text = format!("{} tracks from {}", tracks.len(), file_name(&path));
+                self.library = tracks;
+                self.config.set_all("library", vec![path.display().to_string()]);
+                self.config.save();
+                text
+            }
+            Err(e) => format!("{}: {}", file_name(&path), e),
+        });
+    }
+
+    fn library_ui(&mut self, ctx: &egui::Context) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("library"),
+            egui::ViewportBuilder::default()
+                .with_title("Track Library")
+                .with_inner_size([420.0, 480.0]),
+            |ctx, _| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.label("Drop a rekordbox XML or Mixxx CSV export here.");
+                    if let Some(status) = &self.library_status {
+                        ui.label(status);
+                    }
+                    ui.horizontal(|ui| {
+                        ui.label("Search");
+                        ui.text_edit_singleline(&mut self.library_search);
+                    });
+                    ui.separator();
+                    // a whole library is too many rows to lay out every frame
+                    let (min, max) = (self.bpm_min as f64, self.bpm_max as f64);
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("library").num_columns(2).striped(true).show(ui, |ui| {
+                            for track in library::search(&self.library, &self.library_search).take(200) {
+                                let bpm = (track.bpm * 100.0).round() / 100.0;
+                                let button = ui.add_enabled((min..=max).contains(&bpm), egui::Button::new(bpm_text(bpm)));
+                                if button.on_hover_text("Set the tempo").clicked() {
+                                    clock.request(tempo_request(bpm));
+                                }
+                                ui.label(track.name());
+                                ui.end_row();
+                            }
+                        });
+                    });
+                });
+
+                let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+                if let Some(path) = dropped.into_iter().filter_map(|f| f.path).next() {
+                    self.import_library(path);
+                }
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_library = false;
+                }
+            },
+        );
+    }
+
+    fn use_tempo_map(&mut self) {
+        let Some(map) = self.tempo_map.take() else { return };
+        let mut automation = self.tabs[self.tab].clock.automation.lock().unwrap();
+        automation.points = map.into_iter().map(|(beat, bpm)| Point { beat, bpm, ramp: false }).collect();
+        let last = automation.points.last().map_or(0.0, |p| p.beat);
+        automation.bars = (last / BEATS_PER_BAR as f64).floor() as u32 + 1;
+        automation.enabled = true;
+        automation.looping = false;
+        drop(automation);
+        self.show_automation = true;
+    }
+
+    fn export_session(&mut self, json: bool) {
+        self.export_status = Some(match session::export(json) {
+            Ok(name) => format!("Saved {}", name),
+            Err(e) => e,
+        });
+    }
+
+    fn automation_ui(&mut self, ctx: &egui::Context) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("automation"),
+            egui::ViewportBuilder::default()
+                .with_title("Tempo Automation")
+                .with_inner_size([560.0, 400.0]),
+            |ctx, _| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    let mut automation = clock.automation.lock().unwrap();
+                    let position = clock.position.load(Ordering::SeqCst);
+                    ui.horizontal(|ui| {
+                        ui.checkbox(&mut automation.enabled, "Play automation");
+                        ui.checkbox(&mut automation.looping, "Loop");
+                        ui.label("Bars");
+                        ui.add(egui::DragValue::new(&mut automation.bars).range(1..=256));
+                    });
+                    let range = (self.bpm_min as f64, self.bpm_max as f64);
+                    automation::timeline(ui, &mut automation, &mut self.automation_grab, position as f64 / 24.0, range);
+
+                    // scrubbing relocates the slaves too
+                    let mut bar = position as f64 / TICKS_PER_BAR as f64;
+                    let bars = automation.bars as f64;
+                    if ui.add(egui::Slider::new(&mut bar, 0.0..=bars).text("Bar")).changed() {
+                        clock.request(Request::Locate((bar * TICKS_PER_BAR as f64) as u64));
+                    }
+                    // position and start in one go, the slaves get song position and Continue
+                    if !clock.running.load(Ordering::SeqCst) && ui.button("Start here").clicked() {
+                        clock.request(Request::Start(position));
+                    }
+                    ui.separator();
+
+                    let beats = automation.beats();
+                    let mut remove = None;
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("points").num_columns(4).striped(true).show(ui, |ui| {
+                            ui.label("Beat");
+                            ui.label("BPM");
+                            ui.label("Ramp");
+                            ui.end_row();
+                            for (index, point) in automation.points.iter_mut().enumerate() {
+                                ui.add(egui::DragValue::new(&mut point.beat).range(0.0..=beats).speed(0.25));
+                                ui.add(egui::DragValue::new(&mut point.bpm).range(range.0..=range.1).speed(0.1).max_decimals(1));
+                                ui.checkbox(&mut point.ramp, "");
+                                if ui.small_button("x").clicked() {
+                                    remove = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    if let Some(index) = remove {
+                        automation.points.remove(index);
+                    }
+                    automation.sort();
+                });
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_automation = false;
+                }
+            },
+        );
+        // the playhead moves smoothly, beats alone would make it jump
+        if self.tabs[self.tab].clock.running.load(Ordering::SeqCst) {
+            ctx.request_repaint_after(Duration::from_millis(50));
+        }
+    }
+
+    fn cues_ui(&mut self, ctx: &egui::Context) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("cues"),
+            egui::ViewportBuilder::default()
+                .with_title("Cue List")
+                .with_inner_size([460.0, 340.0]),
+            |ctx, _| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    if clock.running.load(Ordering::SeqCst) {
+                        ui.label(format!("Bar {}", clock.position.load(Ordering::SeqCst) / TICKS_PER_BAR + 1));
+                    } else {
+                        ui.label("Stopped, cues run from Start");
+                    }
+                    ui.separator();
+
+                    let mut cues = clock.cues.lock().unwrap();
+                    let mut remove = None;
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("cues").num_columns(6).striped(true).show(ui, |ui| {
+                            ui.label("At bar");
+                            ui.label("Go to BPM");
+                            ui.label("Over bars");
+                            ui.label("MSC");
+                            ui.label("Cue / list");
+                            ui.end_row();
+                            for (index, cue) in cues.iter_mut().enumerate() {
+                                ui.add(egui::DragValue::new(&mut cue.bar).range(1..=9999));
+                                ui.add(egui::DragValue::new(&mut cue.bpm).range(self.bpm_min..=self.bpm_max));
+                                ui.add(egui::DragValue::new(&mut cue.over).range(0..=64));
+                                egui::ComboBox::from_id_salt(("msc", index))
+                                    .width(70.0)
+                                    .selected_text(cue.msc.as_ref().map_or("-", |m| m.command.name()))
+                                    .show_ui(ui, |ui| {
+                                        if ui.selectable_label(cue.msc.is_none(), "-").clicked() {
+                                            cue.msc = None;
+                                        }
+                                        for command in msc::Command::ALL {
+                                            let current = cue.msc.as_ref().is_some_and(|m| m.command == command);
+                                            if ui.selectable_label(current, command.name()).clicked() {
+                                                cue.msc.get_or_insert_with(msc::Message::new).command = command;
+                                            }
+                                        }
+                                    });
+                                ui.horizontal(|ui| {
+                                    if let Some(m) = &mut cue.msc {
+                                        ui.add(egui::TextEdit::singleline(&mut m.number).desired_width(40.0));
+                                        ui.add(egui::TextEdit::singleline(&mut m.list).desired_width(30.0));
+                                    }
+                                });
+                                if ui.small_button("x").clicked() {
+                                    remove = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    if let Some(index) = remove {
+                        cues.remove(index);
+                    }
+                    if ui.button("Add cue").clicked() {
+                        let bar = cues.iter().map(|c| c.bar + 8).max().unwrap_or(1);
+                        let bpm = clock.bpm.load(Ordering::SeqCst).clamp(self.bpm_min, self.bpm_max);
+                        cues.push(Cue { bar, bpm, over: 0, msc: None });
+                        cues.sort_by_key(|c| c.bar);
+                    }
+                    drop(cues);
+
+                    ui.separator();
+                    ui.horizontal(|ui| {
+                        let mut settings = clock.msc.lock().unwrap();
+                        ui.label("MSC device");
+                        ui.add(egui::DragValue::new(&mut settings.device).range(0..=127));
+                        egui::ComboBox::from_id_salt("msc_format")
+                            .selected_text(msc::format_name(settings.format))
+                            .show_ui(ui, |ui| {
+                                for (format, name) in msc::FORMATS {
+                                    ui.selectable_value(&mut settings.format, format, name);
+                                }
+                            });
+                        // by hand, without a cue number the receiver takes its next cue
+                        for command in [msc::Command::Go, msc::Command::Stop] {
+                            if ui.button(command.name()).clicked() {
+                                let msg = msc::Message { command, ..msc::Message::new() };
+                                clock.outbox.lock().unwrap().push(msg.sysex(&settings));
+                                clock.poke();
+                            }
+                        }
+                    });
+                });
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_cues = false;
+                }
+            },
+        );
+    }
+
+    fn scheduler_ui(&mut self, ctx: &egui::Context) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("scheduler"),
+            egui::ViewportBuilder::default()
+                .with_title("Scheduler")
+                .with_inner_size([420.0, 300.0]),
+            |ctx, _| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.label(format!("Local time {}", schedule::clock_time(SystemTime::now())));
+                    ui.separator();
+
+                    let mut timers = clock.timers.lock().unwrap();
+                    let mut remove = None;
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        egui::Grid::new("timers").num_columns(5).striped(true).show(ui, |ui| {
+                            ui.label("At");
+                            ui.label("Do");
+                            ui.label("Daily");
+                            ui.label("Goes off in");
+                            ui.end_row();
+                            for (index, timer) in timers.iter_mut().enumerate() {
+                                let edit = ui.add(egui::TextEdit::singleline(&mut timer.time).desired_width(70.0));
+                                if edit.changed() {
+                                    timer.schedule();
+                                }
+                                ui.horizontal(|ui| {
+                                    let bpm = clock.bpm.load(Ordering::SeqCst).clamp(self.bpm_min, self.bpm_max);
+                                    egui::ComboBox::from_id_salt(("timer", index))
+                                        .width(70.0)
+                                        .selected_text(timer.action.name())
+                                        .show_ui(ui, |ui| {
+                                            for action in [schedule::Action::Start, schedule::Action::Stop, schedule::Action::Bpm(bpm)] {
+                                                let current = timer.action.name() == action.name();
+                                                if ui.selectable_label(current, action.name()).clicked() && !current {
+                                                    timer.action = action;
+                                                }
+                                            }
+                                        });
+                                    if let schedule::Action::Bpm(bpm) = &mut timer.action {
+                                        ui.add(egui::DragValue::new(bpm).range(self.bpm_min..=self.bpm_max));
+                                    }
+                                });
+                                ui.checkbox(&mut timer.daily, "");
+                                match timer.at {
+                                    Some(at) => ui.label(schedule::countdown(at)),
+                                    None if schedule::parse(&timer.time).is_none() => {
+                                        ui.colored_label(egui::Color32::RED, "HH:MM:SS")
+                                    }
+                                    None => ui.label("done"),
+                                };
+                                if ui.small_button("x").clicked() {
+                                    remove = Some(index);
+                                }
+                                ui.end_row();
+                            }
+                        });
+                    });
+                    if let Some(index) = remove {
+                        timers.remove(index);
+                    }
+                    if ui.button("Add timer").clicked() {
+                        let time = schedule::clock_time(SystemTime::now() + Duration::from_secs(3600));
+                        timers.push(Timer::new(time, schedule::Action::Stop));
+                    }
+                    ui.label("Timers run on this tab's clock, on this computer's local time");
+                });
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_scheduler = false;
+                }
+            },
+        );
+        ctx.request_repaint_after(Duration::from_millis(250));
+    }
+
+    // a hand sweeping round once a bar past a dot per beat, smoother to follow than a flash
+    fn conductor_ui(&mut self, ctx: &egui::Context) {
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("conductor"),
+            egui::ViewportBuilder::default()
+                .with_title("Conductor")
+                .with_inner_size([300.0, 300.0]),
+            |ctx, _| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    // beats into the bar, continuous between ticks
+                    let origin = *clock.origin.lock().unwrap();
+                    let beats = match origin {
+                        Some(origin) if clock.running.load(Ordering::SeqCst) => {
+                            origin.elapsed().as_secs_f64() * clock.tempo() / 60.0 % BEATS_PER_BAR as f64
+                        }
+                        _ => 0.0,
+                    };
+                    let size = ui.available_size().min_elem();
+                    let (rect, _) = ui.allocate_exact_size(egui::vec2(size, size), egui::Sense::hover());
+                    let center = rect.center();
+                    let radius = size / 2.0 - 12.0;
+                    let at = |beats: f64| {
+                        let angle = (beats / BEATS_PER_BAR as f64 * std::f64::consts::TAU) as f32 - std::f32::consts::FRAC_PI_2;
+                        center + egui::vec2(angle.cos(), angle.sin()) * radius
+                    };
+                    let painter = ui.painter();
+                    let lit = ui.visuals().selection.bg_fill;
+                    painter.circle_stroke(center, radius, egui::Stroke::new(2.0, ui.visuals().extreme_bg_color));
+                    for beat in 0..BEATS_PER_BAR {
+                        let current = origin.is_some() && beats as u64 == beat;
+                        let color = match beat {
+                            0 if current => egui::Color32::from_rgb(230, 90, 60),
+                            _ if current => lit,
+                            _ => ui.visuals().extreme_bg_color,
+                        };
+                        painter.circle_filled(at(beat as f64), if beat == 0 { 10.0 } else { 7.0 }, color);
+                    }
+                    painter.line_segment([center, at(beats)], egui::Stroke::new(3.0, lit));
+                });
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_conductor = false;
+                }
+            },
+        );
+        if clock.running.load(Ordering::SeqCst) {
+            ctx.request_repaint();
+        }
+    }
+
+    fn monitor_ui(&mut self, ctx: &egui::Context) {
+        ctx.show_viewport_immediate(
+            egui::ViewportId::from_hash_of("monitor"),
+            egui::ViewportBuilder::default()
+                .with_title("MIDI Monitor")
+                .with_inner_size([420.0, 320.0]),
+            |ctx, _| {
+                egui::CentralPanel::default().show(ctx, |ui| {
+                    ui.horizontal(|ui| {
+                        let selected = match self.in_index {
+                            Some(i) => self.in_names[i].clone(),
+                            None => "No input".to_string(),
+                        };
+                        let mut pick = None;
+                        egui::ComboBox::from_id_salt("input")
+                            .selected_text(selected)
+                            .show_ui(ui, |ui| {
+                                for (index, name) in self.in_names.iter().enumerate() {
+                                    if ui.selectable_label(self.in_index == Some(index), name.clone()).clicked() {
+                                        pick = Some(index);
+                                    }
+                                }
+                            });
+                        if let Some(index) = pick {
+                            self.connect_input(index);
+                        }
+                        if ui.button("Reset").clicked() {
+                            self.monitor.lock().unwrap().reset();
+                        }
+                    });
+
+                    let monitor = self.monitor.lock().unwrap();
+                    ui.separator();
+                    // keeps looking so "No clock" shows once the ticks stop
+                    if monitor.clock_running() {
+                        ctx.request_repaint_after(Duration::from_millis(100));
+                        ui.label(format!(
+                            "Clock  {:.1} BPM   jitter {:.2} ms   drop-outs {}",
+                            monitor.bpm().unwrap_or(0.0),
+                            monitor.jitter_ms().unwrap_or(0.0),
+                            monitor.dropouts,
+                        ));
+                    } else {
+                        ui.label(format!("No clock   drop-outs {}", monitor.dropouts));
+                    }
+                    ui.separator();
+                    egui::ScrollArea::vertical()
+                        .stick_to_bottom(true)
+                        .auto_shrink([false, false])
+                        .show(ui, |ui| {
+                            for line in &monitor.log {
+                                ui.monospace(line);
+                            }
+                        });
+                });
+
+                if ctx.input(|i| i.viewport().close_requested()) {
+                    self.show_monitor = false;
+                }
+            },
+        );
+    }
+}
+
+impl eframe::App for MyApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        let (actions, save) = {
+            let mut learn = self.learn.lock().unwrap();
+            let save = std::mem::take(&mut learn.dirty).then(|| learn.save());
+            (learn.pending.drain(..).collect::<Vec<_>>(), save)
+        };
+        for action in actions {
+            self.run_action(action);
+        }
+        if let Some(map) = save {
+            self.config.set_all("map", map);
+            self.config.save();
+        }
+        let dropped = ctx.input(|i| i.raw.dropped_files.clone());
+        for path in dropped.into_iter().filter_map(|f| f.path) {
+            let ext = path.extension().map(|e| e.to_string_lossy().to_lowercase());
+            if matches!(ext.as_deref(), Some("mid" | "midi")) {
+                self.open_midi_file(path);
+            } else if ext.as_deref() == Some(config::PROFILE_EXT) {
+                self.import_settings(path);
+            } else if matches!(ext.as_deref(), Some("xml" | "csv")) {
+                self.import_library(path);
+                self.show_library = true;
+            } else {
+                self.analyze_file(path);
+            }
+        }
+        if ctx.input_mut(|i| i.consume_key(egui::Modifiers::COMMAND, egui::Key::K)) {
+            self.palette.toggle();
+        }
+        if self.palette.open {
+            let commands = self.commands();
+            if let Some(command) = self.palette.ui(ctx, commands) {
+                self.run_command(command);
+            }
+        }
+        if self.keypad.open && let Some(bpm) = self.keypad.ui(ctx, (self.bpm_min, self.bpm_max)) {
+            self.tabs[self.tab].clock.request(Request::Tempo(bpm));
+        }
+        let clock = Arc::clone(&self.tabs[self.tab].clock);
+        // keys go to the text field while typing a tempo or a command
+        let keys = !ctx.wants_keyboard_input() && !self.palette.open && !self.keypad.open;
+        // text fields have their own undo
+        if keys {
+            let (undo, redo) = ctx.input_mut(|i| {
+                let redo = i.consume_key(egui::Modifiers::COMMAND | egui::Modifiers::SHIFT, egui::Key::Z)
+                    || i.consume_key(egui::Modifiers::COMMAND, egui::Key::Y);
+                (i.consume_key(egui::Modifiers::COMMAND, egui::Key::Z), redo)
+            });
+            if undo {
+                self.undo();
+            }
+            if redo {
+                self.redo();
+            }
+        }
+        if keys && ctx.input(|i| i.key_pressed(egui::Key::Space)) {
+            self.tap();
+        }
+        if keys && self.tabs[self.tab].tapped.is_some() {
+            if ctx.input(|i| i.key_pressed(egui::Key::Enter)) {
+                self.use_tapped();
+            } else if ctx.input(|i| i.key_pressed(egui::Key::Escape)) {
+                self.tabs[self.tab].tapped = None;
+            }
+        }
+        // up and down by 1, right and left by 10, repeating faster the longer they're held
+        let arrows = [(egui::Key::ArrowUp, 1), (egui::Key::ArrowRight, 10), (egui::Key::ArrowDown, -1), (egui::Key::ArrowLeft, -10)];
+        let held = arrows.into_iter().find(|&(key, _)| keys && ctx.input(|i| i.key_down(key)));
+        let now = Instant::now();
+        let steps = self.arrows.steps(held.map(|(key, _)| key), now);
+        if let Some((_, step)) = held {
+            let bpm = clock.bpm.load(Ordering::SeqCst);
+            let mut target = bpm;
+            for _ in 0..steps {
+                if (self.bpm_min..=self.bpm_max).contains(&(target + step)) {
+                    target += step;
+                }
+            }
+            if target != bpm {
+                clock.request(Request::Tempo(target));
+            }
+        }
+        if let Some(wait) = self.arrows.wait(now) {
+            ctx.request_repaint_after(wait);
+        }
+
+        if self.kiosk {
+            self.kiosk_ui(ctx);
+        } else {
+            egui::TopBottomPanel::top("tabs").show(ctx, |ui| {
+                self.tabs_ui(ui);
+            });
+
+            let clock = Arc::clone(&self.tabs[self.tab].clock);
+            egui::CentralPanel::default().show(ctx, |ui| {
+                ui.vertical_centered(|ui| {
+                    let mut value = clock.bpm.load(Ordering::SeqCst);
+                    let source = Source::load(&clock.source);
+                    if source == Source::ExternalMidi {
+                        value = self.pll.lock().unwrap().bpm(Instant::now()).map_or(0, |b| b.round() as i32);
+                    } else if source == Source::Network {
+                        let follower = self.follower.lock().unwrap();
+                        value = if follower.connected() { follower.tempo.round() as i32 } else { 0 };
+                        // a stopped master sends no ticks here to wake us
+                        if !clock.running.load(Ordering::SeqCst) {
+                            ui.ctx().request_repaint_after(Duration::from_millis(250));
+                        }
+                    } else if source == Source::Ltc {
+                        // timecode can start the transport at any moment, the clock thread wakes us
+                        if clock.running.load(Ordering::SeqCst) {
+                            value = clock.tempo().round() as i32;
+                        }
+                    } else if clock.running.load(Ordering::SeqCst) && clock.tempo() > 0.0 {
+                        // automation and cue glides move the tempo under us
+                        value = clock.tempo().round() as i32;
+                    }
+                    if let Some(text) = &mut self.bpm_edit {
+                        let edit = ui.add(
+                            egui::TextEdit::singleline(text)
+                                .font(self.impact_font.clone())
+                                .desired_width(200.0),
+                        );
+                        if !edit.has_focus() && !edit.lost_focus() {
+                            edit.request_focus();
+                        }
+                        // enter commits, escape or clicking away cancels
+                        if edit.lost_focus() {
+                            if ui.ctx().input(|i| i.key_pressed(egui::Key::Enter)) {
+                                match text.trim().parse::<i32>() {
+                                    Ok(bpm) if bpm >= self.bpm_min && bpm <= self.bpm_max => {
+                                        clock.request(Request::Tempo(bpm));
+                                    }
+                                    _ => {}
+                                }
+                            }
+                            self.bpm_edit = None;
+                        }
+                    } else {
+                        // a tapped fraction shows until something else moves the tempo
+                        let manual = clock.manual();
+                        let text = match value {
+                            0 => "--".to_string(),
+                            v if source == Source::Internal && manual.fract() != 0.0 && manual.round() as i32 == v => bpm_text(manual),
+                            v => format!("{}", v),
+                        };
+                        let label = egui::Label::new(
+                            eframe::egui::RichText::new(text).font(self.impact_font.clone()),
+                        )
+                        .sense(egui::Sense::click());
+                        let label = ui.horizontal(|ui| {
+                            let label = ui.add(label).on_hover_text("Click to type, right click for a keypad");
+                            if let Some(name) = self.markings.name(value).filter(|_| self.markings.show && value != 0) {
+                                ui.label(egui::RichText::new(name).size(24.0).italics());
+                            }
+                            label
+                        }).inner;
+                        if label.clicked() {
+                            self.bpm_edit = Some(if value != 0 { value.to_string() } else { String::new() });
+                        }
+                        if label.secondary_clicked() {
+                            self.keypad.show();
+                        }
+                    }
+
+                    if let Some((tapped, _)) = self.tabs[self.tab].tapped {
+                        let (confirm, dismiss) = ui.horizontal(|ui| {
+                            ui.label(egui::RichText::new(format!("Tapped {}", bpm_text(tapped))).size(20.0));
+                            (ui.small_button("Use").clicked(), ui.small_button("x").clicked())
+                        }).inner;
+                        if confirm {
+                            self.use_tapped();
+                        } else if dismiss {
+                            self.tabs[self.tab].tapped = None;
+                        }
+                    }
+
+                    let mut incident = clock.incident.lock().unwrap();
+                    if let Some(text) = incident.as_ref() {
+                        let dismiss = ui.horizontal(|ui| {
+                            ui.colored_label(egui::Color32::RED, text.as_str());
+                            ui.small_button("x").clicked()
+                        });
+                        if dismiss.inner {
+                            *incident = None;
+                        }
+                    }
+                    drop(incident);
+
+                    if let Some((port, bpm)) = self.recall {
+                        let (use_it, dismiss) = ui.horizontal(|ui| {
+                            ui.label(format!("{} was last at {} BPM", self.parrot_names[port], bpm));
+                            (ui.small_button("Use").clicked(), ui.small_button("x").clicked())
+                        }).inner;
+                        if use_it {
+                            clock.request(Request::Tempo(bpm));
+                        }
+                        if use_it || dismiss {
+                            self.recall = None;
+                        }
+                    }
+
+                    let mut analysis = self.analysis.lock().unwrap();
+                    let mut use_map = false;
+                    if ctx.input(|i| !i.raw.hovered_files.is_empty()) {
+                        ui.label("Drop a WAV or MIDI file to set the tempo from it");
+                    } else if let Some(text) = analysis.as_ref() {
+                        let dismiss = ui.horizontal(|ui| {
+                            ui.label(text.as_str());
+                            if self.tempo_map.is_some() && ui.small_button("Use tempo map").clicked() {
+                                use_map = true;
+                            }
+                            ui.small_button("x").clicked()
+                        });
+                        if dismiss.inner || use_map {
+                            *analysis = None;
+                            if !use_map {
+                                self.tempo_map = None;
+                            }
+                        }
+                    }
+                    drop(analysis);
+                    if use_map {
+                        self.use_tempo_map();
+                    }
+                    self.bar_ui(ui);
+                    self.phrase_ui(ui);
+                    if self.show_status {
+                        self.status_ui(ui);
+                    }
+                    if self.show_slots {
+                        self.slots_ui(ui);
+                    }
+                    if self.show_sweep {
+                        self.sweep_ui(ui);
+                    }
+
+                    ui.separator();
+                    self.mute_ui(ui);
+                    ui.horizontal_centered(|ui| {
+                    self.outputs_ui(ui);
+                    self.source_ui(ui);
+                    self.transport_ui(ui);
+                    ui.menu_button("Menu", |ui| {
+                        ui.checkbox(&mut self.show_monitor, "Monitor");
+                        ui.checkbox(&mut self.show_automation, "Automation");
+                        ui.checkbox(&mut self.show_cues, "Cue list");
+                        ui.checkbox(&mut self.show_scheduler, "Scheduler");
+                        ui.checkbox(&mut self.show_conductor, "Conductor");
+                        ui.checkbox(&mut self.show_library, "Track library");
+                        ui.checkbox(&mut self.show_status, "Status");
+                        ui.checkbox(&mut self.show_slots, "A/B tempos");
+                        ui.checkbox(&mut self.show_sweep, "Tempo knob");
+                        ui.checkbox(&mut self.show_learn, "MIDI learn");
+                        ui.checkbox(&mut self.show_settings, "Settings");
+                    });
+                    });
+                });
+            });
+        }
+
+        if self.show_monitor {
+            self.monitor_ui(ctx);
+        }
+        if self.show_settings {
+            self.settings_ui(ctx);
+        }
+        if self.show_learn {
+            self.learn_ui(ctx);
+        }
+        self.update_audio();
+        self.update_network();
+        let running = self.tabs.iter().any(|t| t.clock.running.load(Ordering::SeqCst));
+        self.awake.set(self.keep_awake && running);
+        self.steer();
+        if self.show_automation {
+            self.automation_ui(ctx);
+        }
+        if self.show_cues {
+            self.cues_ui(ctx);
+        }
+        if self.show_scheduler {
+            self.scheduler_ui(ctx);
+        }
+        if self.show_library {
+            self.library_ui(ctx);
+        }
+        if self.show_conductor {
+            self.conductor_ui(ctx);
+        }
+        self.remember_tempos();
+        // tempo the clock threads moved by themselves isn't an undo step. the count is
+        // read either side so a change landing in between is caught
+        let before: Vec<u64> = self.tabs.iter().map(|t| t.clock.moved.load(Ordering::SeqCst)).collect();
+        let snapshot = self.snapshot();
+        let mut moved = false;
+        for (tab, before) in self.tabs.iter_mut().zip(before) {
+            let after = tab.clock.moved.load(Ordering::SeqCst);
+            moved |= std::mem::replace(&mut tab.moved, after) != before || before != after;
+        }
+        if moved {
+            self.history.rebase(snapshot);
+        } else {
+            self.history.observe(snapshot, Instant::now());
+        }
+    }
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map_or_else(|| path.display().to_string(), |n| n.to_string_lossy().into_owned())
+}
+
+// whole tempos as they are, others with as many decimals as they need
+fn bpm_text(bpm: f64) -> String {
+    let text = format!("{:.2}", bpm);
+    text.trim_end_matches('0').trim_end_matches('.').to_string()
+}
+
+// taps between whole BPM keep their fraction on the clock
+fn tempo_request(bpm: f64) -> Request {
+    if bpm.fract() == 0.0 { Request::Tempo(bpm as i32) } else { Request::Exact(bpm) }
+}
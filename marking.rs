@@ -0,0 +1,53 @@
+// the usual Italian tempo markings, each from its BPM until the next one starts.
+// sources disagree on the edges, so these can be changed in settings
+const DEFAULT: [(i32, &str); 11] = [
+    (0, "Larghissimo"),
+    (25, "Grave"),
+    (40, "Largo"),
+    (60, "Larghetto"),
+    (66, "Adagio"),
+    (76, "Andante"),
+    (108, "Moderato"),
+    (120, "Allegro"),
+    (156, "Vivace"),
+    (168, "Presto"),
+    (200, "Prestissimo"),
+];
+
+#[derive(Clone, PartialEq)]
+pub struct Markings {
+    pub show: bool,
+    // by starting tempo, once loaded
+    pub ranges: Vec<(i32, String)>,
+}
+
+impl Markings {
+    pub fn new() -> Self {
+        Self { show: false, ranges: DEFAULT.iter().map(|&(from, name)| (from, name.to_string())).collect() }
+    }
+
+    // kept in any order while being edited
+    pub fn name(&self, bpm: i32) -> Option<&str> {
+        self.ranges.iter().filter(|(from, _)| *from <= bpm).max_by_key(|(from, _)| *from).map(|(_, name)| name.as_str())
+    }
+
+    // "from;name" lines, the defaults when there are none
+    pub fn from_config(show: Option<&str>, lines: &[&str]) -> Self {
+        let mut markings = Self::new();
+        markings.show = show == Some("on");
+        let ranges: Vec<(i32, String)> = lines
+            .iter()
+            .filter_map(|l| l.split_once(';'))
+            .filter_map(|(from, name)| Some((from.trim().parse().ok()?, name.trim().to_string())))
+            .collect();
+        if !ranges.is_empty() {
+            markings.ranges = ranges;
+            markings.ranges.sort_by_key(|(from, _)| *from);
+        }
+        markings
+    }
+
+    pub fn to_config(&self) -> Vec<String> {
+        self.ranges.iter().map(|(from, name)| format!("{};{}", from, name)).collect()
+    }
+}
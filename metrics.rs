@@ -0,0 +1,139 @@
+use std::collections::VecDeque;
+use std::fmt::Write as _;
+use std::io::{ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::clock::Clock;
+
+// lateness is summarized over this many recent ticks
+const WINDOW: usize = 2400;
+
+// counters the clock thread keeps for the metrics endpoint
+pub struct Stats {
+    pub ticks: AtomicU64,
+    pub reconnects: AtomicU64,
+    // seconds each tick went out after its scheduled time
+    lateness: Mutex<VecDeque<f64>>,
+}
+
+impl Stats {
+    pub fn new() -> Self {
+        Self { ticks: AtomicU64::new(0), reconnects: AtomicU64::new(0), lateness: Mutex::new(VecDeque::new()) }
+    }
+
+    pub fn late(&self, secs: f64) {
+        // a scrape in progress can have this one
+        let Ok(mut lateness) = self.lateness.try_lock() else {
+            return;
+        };
+        if lateness.len() >= WINDOW {
+            lateness.pop_front();
+        }
+        lateness.push_back(secs);
+    }
+
+    // p50, p99, max and standard deviation
+    fn summary(&self) -> Option<(f64, f64, f64, f64)> {
+        let mut late: Vec<f64> = self.lateness.lock().unwrap().iter().copied().collect();
+        if late.is_empty() {
+            return None;
+        }
+        late.sort_by(f64::total_cmp);
+        let at = |p: f64| late[((late.len() - 1) as f64 * p).round() as usize];
+        let mean = late.iter().sum::<f64>() / late.len() as f64;
+        let sd = (late.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / late.len() as f64).sqrt();
+        Some((at(0.5), at(0.99), at(1.0), sd))
+    }
+}
+
+// Prometheus text format on http://<host>:<port>/metrics, stops when dropped
+pub struct Server {
+    stop: Arc<AtomicBool>,
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+    }
+}
+
+pub fn serve(port: u16, clocks: Arc<Mutex<Vec<Arc<Clock>>>>, started: Instant) -> Result<Server, String> {
+    let listener = TcpListener::bind(("0.0.0.0", port)).map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let stopped = Arc::clone(&stop);
+    thread::spawn(move || {
+        while !stopped.load(Ordering::SeqCst) {
+            match listener.accept() {
+                Ok((stream, _)) => {
+                    let body = render(&clocks.lock().unwrap(), started);
+                    respond(stream, &body);
+                }
+                Err(e) if e.kind() == ErrorKind::WouldBlock => thread::sleep(Duration::from_millis(100)),
+                Err(e) => eprintln!("Metrics connection failed: {}", e),
+            }
+        }
+    });
+    Ok(Server { stop })
+}
+
+// answers whatever was asked, there's only the one page
+fn respond(mut stream: TcpStream, body: &str) {
+    let _ = stream.set_nonblocking(false);
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(1)));
+    let _ = stream.read(&mut [0; 1024]);
+    let _ = write!(
+        stream,
+        "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        body.len(),
+        body,
+    );
+}
+
+fn render(clocks: &[Arc<Clock>], started: Instant) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "# HELP midiclock_uptime_seconds Seconds since midiclock started.");
+    let _ = writeln!(out, "# TYPE midiclock_uptime_seconds gauge");
+    let _ = writeln!(out, "midiclock_uptime_seconds {:.3}", started.elapsed().as_secs_f64());
+
+    let metric = |out: &mut String, name: &str, kind: &str, help: &str, value: &dyn Fn(&Clock) -> Option<String>| {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        for clock in clocks {
+            if let Some(value) = value(clock) {
+                let _ = writeln!(out, "{}{{clock=\"{}\"}} {}", name, clock.name, value);
+            }
+        }
+    };
+    metric(&mut out, "midiclock_ticks_sent_total", "counter", "MIDI clock ticks sent.", &|c| {
+        Some(c.stats.ticks.load(Ordering::SeqCst).to_string())
+    });
+    metric(&mut out, "midiclock_port_reconnects_total", "counter", "Output ports reconnected after failing.", &|c| {
+        Some(c.stats.reconnects.load(Ordering::SeqCst).to_string())
+    });
+    metric(&mut out, "midiclock_tempo_bpm", "gauge", "Tempo the clock is running at.", &|c| Some(format!("{:.3}", c.tempo())));
+    metric(&mut out, "midiclock_running", "gauge", "1 while the transport runs.", &|c| {
+        Some((c.running.load(Ordering::SeqCst) as u8).to_string())
+    });
+    metric(&mut out, "midiclock_tick_jitter_seconds", "gauge", "Standard deviation of tick lateness.", &|c| {
+        c.stats.summary().map(|(_, _, _, sd)| format!("{:.9}", sd))
+    });
+
+    let _ = writeln!(out, "# HELP midiclock_tick_lateness_seconds How late ticks went out, over the last {} ticks.", WINDOW);
+    let _ = writeln!(out, "# TYPE midiclock_tick_lateness_seconds gauge");
+    for clock in clocks {
+        if let Some((p50, p99, max, _)) = clock.stats.summary() {
+            for (quantile, value) in [("0.5", p50), ("0.99", p99), ("1", max)] {
+                let _ = writeln!(
+                    out,
+                    "midiclock_tick_lateness_seconds{{clock=\"{}\",quantile=\"{}\"}} {:.9}",
+                    clock.name, quantile, value,
+                );
+            }
+        }
+    }
+    out
+}
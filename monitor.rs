@@ -0,0 +1,198 @@
+use std::collections::VecDeque;
+use std::time::Instant;
+
+const LOG_LEN: usize = 200;
+// 4 beats worth of clock ticks
+const CLOCK_WINDOW: usize = 96;
+// a gap this long means the master stopped, not that it dropped ticks
+const CLOCK_TIMEOUT: f64 = 0.5;
+// a gap this close to a whole number of ticks is dropped ones, at most this many
+const SLACK: f64 = 0.15;
+const MAX_DROPPED: f64 = 3.0;
+// this many such gaps in a row are the master slowing down
+const DROP_RUN: usize = 3;
+
+pub struct Monitor {
+    pub log: VecDeque<String>,
+    pub dropouts: u32,
+    started: Instant,
+    last_clock: Option<Instant>,
+    intervals: VecDeque<f64>,
+    // the drop-out gaps in a row so far
+    dropped: Vec<f64>,
+}
+
+impl Monitor {
+    pub fn new() -> Self {
+        Self {
+            log: VecDeque::new(),
+            dropouts: 0,
+            started: Instant::now(),
+            last_clock: None,
+            intervals: VecDeque::new(),
+            dropped: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, msg: &[u8]) {
+        let now = Instant::now();
+        // clock is analyzed, not logged, it would flood the view
+        if msg.first() == Some(&0xF8) {
+            self.clock(now);
+            return;
+        }
+        let t = now.duration_since(self.started).as_secs_f64();
+        self.log.push_back(format!("{:>9.3}  {}", t, decode(msg)));
+        while self.log.len() > LOG_LEN {
+            self.log.pop_front();
+        }
+    }
+
+    fn clock(&mut self, now: Instant) {
+        if let Some(last) = self.last_clock {
+            let dt = now.duration_since(last).as_secs_f64();
+            if dt > CLOCK_TIMEOUT {
+                self.intervals.clear();
+                self.dropped.clear();
+            } else if self.mean().is_some_and(|mean| dropout(dt, mean)) {
+                // a missing tick shows up as a double length gap, keep it out of the stats
+                self.dropouts += 1;
+                self.dropped.push(dt);
+                if self.dropped.len() >= DROP_RUN {
+                    // too many in a row, they were the ticks of a slower tempo after all
+                    self.dropouts -= self.dropped.len() as u32;
+                    self.intervals.clear();
+                    for dt in std::mem::take(&mut self.dropped) {
+                        self.interval(dt);
+                    }
+                }
+            } else {
+                self.dropped.clear();
+                self.interval(dt);
+            }
+        }
+        self.last_clock = Some(now);
+    }
+
+    fn interval(&mut self, dt: f64) {
+        self.intervals.push_back(dt);
+        while self.intervals.len() > CLOCK_WINDOW {
+            self.intervals.pop_front();
+        }
+    }
+
+    fn mean(&self) -> Option<f64> {
+        if self.intervals.len() < 2 {
+            return None;
+        }
+        Some(self.intervals.iter().sum::<f64>() / self.intervals.len() as f64)
+    }
+
+    pub fn clock_running(&self) -> bool {
+        self.last_clock
+            .is_some_and(|last| last.elapsed().as_secs_f64() < CLOCK_TIMEOUT)
+    }
+
+    pub fn bpm(&self) -> Option<f64> {
+        self.mean().map(|mean| 60.0 / (mean * 24.0))
+    }
+
+    // standard deviation of the tick interval
+    pub fn jitter_ms(&self) -> Option<f64> {
+        let mean = self.mean()?;
+        let var = self.intervals.iter().map(|dt| (dt - mean).powi(2)).sum::<f64>()
+            / self.intervals.len() as f64;
+        Some(var.sqrt() * 1000.0)
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+}
+
+fn dropout(dt: f64, mean: f64) -> bool {
+    let n = (dt / mean).round();
+    (2.0..=MAX_DROPPED + 1.0).contains(&n) && (dt - n * mean).abs() < SLACK * mean
+}
+
+pub fn decode(msg: &[u8]) -> String {
+    let hex: Vec<String> = msg.iter().take(8).map(|b| format!("{:02X}", b)).collect();
+    let hex = if msg.len() > 8 { format!("{} ..", hex.join(" ")) } else { hex.join(" ") };
+    let data = |i: usize| msg.get(i).copied().unwrap_or(0);
+    let status = data(0);
+    let ch = (status & 0x0F) + 1;
+
+    let text = match status {
+        0x80..=0x8F => format!("Note Off    ch{} {} vel {}", ch, data(1), data(2)),
+        0x90..=0x9F => format!("Note On     ch{} {} vel {}", ch, data(1), data(2)),
+        0xA0..=0xAF => format!("Aftertouch  ch{} {} {}", ch, data(1), data(2)),
+        0xB0..=0xBF => format!("CC          ch{} {} = {}", ch, data(1), data(2)),
+        0xC0..=0xCF => format!("Program     ch{} {}", ch, data(1)),
+        0xD0..=0xDF => format!("Pressure    ch{} {}", ch, data(1)),
+        0xE0..=0xEF => {
+            let bend = ((data(2) as i32) << 7 | data(1) as i32) - 8192;
+            format!("Pitch Bend  ch{} {}", ch, bend)
+        }
+        0xF0 => format!("SysEx       {} bytes", msg.len()),
+        0xF1 => format!("MTC QF      {}", data(1)),
+        0xF2 => format!("Song Pos    {}", (data(2) as u32) << 7 | data(1) as u32),
+        0xF3 => format!("Song Select {}", data(1)),
+        0xF6 => "Tune Request".to_string(),
+        0xF8 => "Clock".to_string(),
+        0xFA => "Start".to_string(),
+        0xFB => "Continue".to_string(),
+        0xFC => "Stop".to_string(),
+        0xFE => "Active Sensing".to_string(),
+        0xFF => "Reset".to_string(),
+        _ => "?".to_string(),
+    };
+    format!("{:<12} {}", hex, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reclock;
+
+    // `bpm` for `beats`, starting at `t`, returns where it left off
+    fn feed(monitor: &mut Monitor, t: Instant, bpm: f64, beats: u32) -> Instant {
+        let period = 60.0 / (bpm * 24.0);
+        let mut t = t;
+        for _ in 0..beats * 24 {
+            t = reclock::shift(t, period);
+            monitor.clock(t);
+        }
+        t
+    }
+
+    #[test]
+    fn dropped_tick() {
+        let mut monitor = Monitor::new();
+        let t = feed(&mut monitor, Instant::now(), 120.0, 2);
+        let t = reclock::shift(t, 2.0 * 60.0 / (120.0 * 24.0));
+        monitor.clock(t);
+        feed(&mut monitor, t, 120.0, 1);
+        assert_eq!(monitor.dropouts, 1);
+        assert!((monitor.bpm().unwrap() - 120.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn halved_tempo() {
+        let mut monitor = Monitor::new();
+        let t = feed(&mut monitor, Instant::now(), 120.0, 2);
+        feed(&mut monitor, t, 60.0, 4);
+        assert_eq!(monitor.dropouts, 0);
+        let bpm = monitor.bpm().unwrap();
+        assert!((bpm - 60.0).abs() < 0.01, "measured {} BPM", bpm);
+    }
+
+    #[test]
+    fn slower_tempo() {
+        let mut monitor = Monitor::new();
+        let t = feed(&mut monitor, Instant::now(), 120.0, 2);
+        feed(&mut monitor, t, 70.0, 4);
+        assert_eq!(monitor.dropouts, 0);
+        let bpm = monitor.bpm().unwrap();
+        assert!((bpm - 70.0).abs() < 0.01, "measured {} BPM", bpm);
+    }
+}
@@ -0,0 +1,182 @@
+use std::io::{Read, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::clock::{Clock, BEATS_PER_BAR};
+
+const KEEP_ALIVE: u16 = 60;
+const RETRY: Duration = Duration::from_secs(5);
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+pub struct Mqtt {
+    pub enabled: bool,
+    // host:port, 1883 if the port is left off
+    pub broker: String,
+    // topics are <prefix>/tempo, /beat, /bar and /transport
+    pub prefix: String,
+    // written by the publisher thread
+    pub status: String,
+}
+
+impl Mqtt {
+    pub fn new(prefix: String) -> Self {
+        Self { enabled: false, broker: String::new(), prefix, status: String::new() }
+    }
+}
+
+pub enum Event {
+    Tempo(f64),
+    // position in ticks since Start
+    Beat(u64),
+    Transport(bool),
+}
+
+// publishing happens on its own thread so a slow broker can't hold up the clock,
+// it ends when the sender is dropped
+pub fn spawn(clock: Arc<Clock>) -> Sender<Event> {
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || publisher(clock, rx));
+    tx
+}
+
+fn publisher(clock: Arc<Clock>, rx: Receiver<Event>) {
+    let mut stream: Option<TcpStream> = None;
+    let mut broker = String::new();
+    let mut retry: Option<Instant> = None;
+    let mut last_sent = Instant::now();
+    loop {
+        // wakes up every second to pick up settings changes and keep the connection alive
+        let event = match rx.recv_timeout(Duration::from_secs(1)) {
+            Ok(event) => Some(event),
+            Err(RecvTimeoutError::Timeout) => None,
+            Err(RecvTimeoutError::Disconnected) => break,
+        };
+        let (enabled, wanted, prefix) = {
+            let mqtt = clock.mqtt.lock().unwrap();
+            (mqtt.enabled, mqtt.broker.trim().to_string(), mqtt.prefix.trim_end_matches('/').to_string())
+        };
+        if !enabled || wanted != broker {
+            if let Some(mut s) = stream.take() {
+                let _ = s.write_all(&[0xE0, 0x00]);
+            }
+            broker = wanted;
+            retry = None;
+            if !enabled {
+                set_status(&clock, "");
+                continue;
+            }
+        }
+        if stream.is_none() && !broker.is_empty() && retry.is_none_or(|t| Instant::now() >= t) {
+            match connect(&broker, &clock.name) {
+                Ok(s) => {
+                    stream = Some(s);
+                    last_sent = Instant::now();
+                    set_status(&clock, "Connected");
+                }
+                Err(e) => {
+                    set_status(&clock, &e);
+                    retry = Some(Instant::now() + RETRY);
+                }
+            }
+        }
+        let Some(s) = stream.as_mut() else {
+            continue;
+        };
+        let packets = match event {
+            Some(Event::Tempo(bpm)) => vec![publish(&format!("{}/tempo", prefix), &format!("{:.2}", bpm), true)],
+            Some(Event::Beat(position)) => {
+                let beat = position / 24;
+                let mut packets = vec![publish(&format!("{}/beat", prefix), &(beat % BEATS_PER_BAR + 1).to_string(), false)];
+                if beat.is_multiple_of(BEATS_PER_BAR) {
+                    packets.push(publish(&format!("{}/bar", prefix), &(beat / BEATS_PER_BAR + 1).to_string(), false));
+                }
+                packets
+            }
+            Some(Event::Transport(running)) => {
+                vec![publish(&format!("{}/transport", prefix), if running { "start" } else { "stop" }, true)]
+            }
+            None if last_sent.elapsed() >= Duration::from_secs(KEEP_ALIVE as u64 / 2) => vec![vec![0xC0, 0x00]],
+            None => Vec::new(),
+        };
+        for packet in packets {
+            if let Err(e) = s.write_all(&packet) {
+                set_status(&clock, &format!("Disconnected: {}", e));
+                stream = None;
+                retry = Some(Instant::now() + RETRY);
+                break;
+            }
+            last_sent = Instant::now();
+        }
+    }
+    if let Some(mut s) = stream {
+        let _ = s.write_all(&[0xE0, 0x00]);
+    }
+}
+
+fn set_status(clock: &Clock, status: &str) {
+    clock.mqtt.lock().unwrap().status = status.to_string();
+}
+
+fn connect(broker: &str, name: &str) -> Result<TcpStream, String> {
+    let address = if broker.contains(':') { broker.to_string() } else { format!("{}:1883", broker) };
+    let addr = address
+        .to_socket_addrs()
+        .map_err(|e| e.to_string())?
+        .next()
+        .ok_or("Broker not found")?;
+    let mut stream = TcpStream::connect_timeout(&addr, TIMEOUT).map_err(|e| e.to_string())?;
+    stream.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+    stream.set_write_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+    let _ = stream.set_nodelay(true);
+
+    // MQTT 3.1.1, clean session, no will, no login
+    let mut body = string("MQTT");
+    body.extend([4, 0x02]);
+    body.extend(KEEP_ALIVE.to_be_bytes());
+    let id: String = format!("midiclock-{}-{}", std::process::id(), name)
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .take(23)
+        .collect();
+    body.extend(string(&id));
+    stream.write_all(&packet(0x10, body)).map_err(|e| e.to_string())?;
+
+    let mut ack = [0u8; 4];
+    stream.read_exact(&mut ack).map_err(|e| e.to_string())?;
+    match ack {
+        [0x20, 0x02, _, 0] => Ok(stream),
+        [0x20, 0x02, _, code] => Err(format!("Broker refused the connection ({})", code)),
+        _ => Err("Not an MQTT broker".to_string()),
+    }
+}
+
+// QoS 0
+fn publish(topic: &str, payload: &str, retain: bool) -> Vec<u8> {
+    let mut body = string(topic);
+    body.extend(payload.as_bytes());
+    packet(0x30 | retain as u8, body)
+}
+
+fn packet(header: u8, body: Vec<u8>) -> Vec<u8> {
+    let mut p = vec![header];
+    // remaining length, 7 bits at a time
+    let mut len = body.len();
+    loop {
+        let byte = (len % 128) as u8;
+        len /= 128;
+        p.push(if len > 0 { byte | 0x80 } else { byte });
+        if len == 0 {
+            break;
+        }
+    }
+    p.extend(body);
+    p
+}
+
+fn string(s: &str) -> Vec<u8> {
+    let mut v = (s.len() as u16).to_be_bytes().to_vec();
+    v.extend(s.as_bytes());
+    v
+}
@@ -0,0 +1,90 @@
+// MIDI Show Control, the SysEx theatre rigs take their cues from
+#[derive(Clone, Copy, PartialEq)]
+pub enum Command {
+    Go,
+    Stop,
+    Resume,
+}
+
+impl Command {
+    pub const ALL: [Command; 3] = [Command::Go, Command::Stop, Command::Resume];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::Go => "GO",
+            Command::Stop => "STOP",
+            Command::Resume => "RESUME",
+        }
+    }
+
+    fn code(self) -> u8 {
+        match self {
+            Command::Go => 0x01,
+            Command::Stop => 0x02,
+            Command::Resume => 0x03,
+        }
+    }
+}
+
+// the general command format of each device type
+pub const FORMATS: [(u8, &str); 8] = [
+    (0x7F, "All types"),
+    (0x01, "Lighting"),
+    (0x10, "Sound"),
+    (0x20, "Machinery"),
+    (0x30, "Video"),
+    (0x40, "Projection"),
+    (0x50, "Process control"),
+    (0x60, "Pyro"),
+];
+
+pub fn format_name(format: u8) -> &'static str {
+    FORMATS.iter().find(|(f, _)| *f == format).map_or("Other", |(_, name)| name)
+}
+
+// who the cues are addressed to
+pub struct Settings {
+    // 0x7F is everyone
+    pub device: u8,
+    pub format: u8,
+}
+
+impl Settings {
+    pub fn new() -> Self {
+        Self { device: 0x7F, format: 0x7F }
+    }
+}
+
+// sent when its cue in the cue list fires
+pub struct Message {
+    pub command: Command,
+    // empty means the next cue, as far as the receiver is concerned
+    pub number: String,
+    pub list: String,
+}
+
+impl Message {
+    pub fn new() -> Self {
+        Self { command: Command::Go, number: String::new(), list: String::new() }
+    }
+
+    pub fn sysex(&self, settings: &Settings) -> Vec<u8> {
+        let mut msg = vec![0xF0, 0x7F, settings.device & 0x7F, 0x02, settings.format & 0x7F, self.command.code()];
+        let number = ascii(&self.number);
+        if !number.is_empty() {
+            msg.extend(number);
+            let list = ascii(&self.list);
+            if !list.is_empty() {
+                msg.push(0x00);
+                msg.extend(list);
+            }
+        }
+        msg.push(0xF7);
+        msg
+    }
+}
+
+// cue numbers are digits and decimal points
+fn ascii(s: &str) -> Vec<u8> {
+    s.bytes().filter(|b| b.is_ascii_digit() || *b == b'.').collect()
+}
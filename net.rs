@@ -0,0 +1,160 @@
+use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+use crate::reclock;
+
+pub const PORT: u16 = 21930;
+const MAGIC: &[u8; 4] = b"MCK1";
+// a packet every this many ticks, a 16th
+const EVERY: u64 = 6;
+// free-wheels on the last lock through this many seconds without packets
+const DROPOUT: f64 = 1.0;
+// packets only ever arrive late, so an earlier one is taken at once and a later
+// one mostly means delay, the grid only creeps toward it for drift between the machines
+const ALPHA: f64 = 0.05;
+// further off than this the master relocated
+const JUMP: f64 = 0.05;
+
+// one tab's clock sent out to the LAN, other instances follow with the Network source
+pub struct Master {
+    pub enabled: bool,
+    // empty broadcasts
+    pub target: String,
+    pub port: u16,
+}
+
+impl Master {
+    pub fn new() -> Self {
+        Self { enabled: false, target: String::new(), port: PORT }
+    }
+
+    fn address(&self) -> SocketAddrV4 {
+        let target = self.target.trim().parse::<Ipv4Addr>().unwrap_or(Ipv4Addr::BROADCAST);
+        SocketAddrV4::new(target, self.port)
+    }
+}
+
+// lives on the clock thread
+pub struct Sender {
+    socket: Option<UdpSocket>,
+    count: u64,
+}
+
+impl Sender {
+    pub fn new() -> Self {
+        Self { socket: None, count: 0 }
+    }
+
+    // call right as each tick goes out, with that tick's position
+    pub fn tick(&mut self, master: &Master, running: bool, tempo: f64, position: u64) {
+        self.count += 1;
+        if !master.enabled || !self.count.is_multiple_of(EVERY) {
+            return;
+        }
+        let mut packet = MAGIC.to_vec();
+        packet.extend([running as u8, 0, 0, 0]);
+        packet.extend(tempo.to_be_bytes());
+        packet.extend(position.to_be_bytes());
+        if self.socket.is_none() {
+            self.socket = UdpSocket::bind("0.0.0.0:0").ok();
+            if let Some(socket) = &self.socket {
+                let _ = socket.set_broadcast(true);
+            }
+        }
+        if let Some(socket) = &self.socket {
+            let _ = socket.send_to(&packet, master.address());
+        }
+    }
+}
+
+// what the slaves hear, shared by every tab on the Network source
+pub struct Follower {
+    pub port: u16,
+    pub master: Option<SocketAddr>,
+    pub tempo: f64,
+    pub running: bool,
+    // when tick 0 was on the master's grid
+    zero: Option<Instant>,
+    last: Option<Instant>,
+}
+
+impl Follower {
+    pub fn new() -> Self {
+        Self { port: PORT, master: None, tempo: 0.0, running: false, zero: None, last: None }
+    }
+
+    fn packet(&mut self, at: Instant, from: SocketAddr, running: bool, tempo: f64, position: u64) {
+        let period = 60.0 / (tempo * 24.0);
+        let zero = reclock::shift(at, -(position as f64 * period));
+        self.zero = Some(match self.zero {
+            Some(z) if self.master == Some(from) && self.tempo == tempo => {
+                let late = reclock::secs_between(z, zero);
+                match late {
+                    l if !(0.0..=JUMP).contains(&l) => zero,
+                    l => reclock::shift(z, l * ALPHA),
+                }
+            }
+            _ => zero,
+        });
+        self.master = Some(from);
+        self.tempo = tempo;
+        self.running = running;
+        self.last = Some(at);
+    }
+
+    // when the master's tick 0 was and its tick period. None while it's stopped or gone
+    pub fn lock_point(&self, now: Instant) -> Option<(Instant, f64)> {
+        let last = self.last?;
+        if !self.running || self.tempo <= 0.0 || reclock::secs_between(last, now) > DROPOUT {
+            return None;
+        }
+        Some((self.zero?, 60.0 / (self.tempo * 24.0)))
+    }
+
+    pub fn connected(&self) -> bool {
+        self.last.is_some_and(|t| t.elapsed().as_secs_f64() <= DROPOUT)
+    }
+}
+
+// the receiving socket's thread, stops on drop
+pub struct Listener {
+    stop: Arc<AtomicBool>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Drop for Listener {
+    fn drop(&mut self) {
+        self.stop.store(true, Ordering::SeqCst);
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
+
+pub fn listen(follower: Arc<Mutex<Follower>>) -> Result<Listener, String> {
+    let port = follower.lock().unwrap().port;
+    let socket = UdpSocket::bind(("0.0.0.0", port)).map_err(|e| format!("Failed to listen on port {}: {}", port, e))?;
+    socket.set_read_timeout(Some(Duration::from_millis(200))).map_err(|e| e.to_string())?;
+    let stop = Arc::new(AtomicBool::new(false));
+    let thread_stop = Arc::clone(&stop);
+    let thread = thread::spawn(move || {
+        let mut buf = [0u8; 64];
+        while !thread_stop.load(Ordering::SeqCst) {
+            let Ok((len, from)) = socket.recv_from(&mut buf) else {
+                continue;
+            };
+            let at = Instant::now();
+            if len != 24 || &buf[0..4] != MAGIC {
+                continue;
+            }
+            let tempo = f64::from_be_bytes(buf[8..16].try_into().unwrap());
+            let position = u64::from_be_bytes(buf[16..24].try_into().unwrap());
+            if tempo > 0.0 && tempo.is_finite() {
+                follower.lock().unwrap().packet(at, from, buf[4] != 0, tempo, position);
+            }
+        }
+    });
+    Ok(Listener { stop, thread: Some(thread) })
+}
@@ -0,0 +1,107 @@
+use eframe::egui;
+use crate::learn::Action;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Window {
+    Monitor,
+    Automation,
+    Cues,
+    Scheduler,
+    Conductor,
+    Library,
+    Learn,
+    Settings,
+}
+
+#[derive(Clone, Copy)]
+pub enum Command {
+    Action(Action),
+    SetBpm(i32),
+    ToggleOutput(usize),
+    // None is the internal clock, otherwise an input port
+    Source(Option<usize>),
+    ChaseLtc,
+    FollowNetwork,
+    Show(Window),
+    NewTab,
+    CloseTab,
+    // true for JSON, false for CSV
+    Export(bool),
+    Undo,
+    Redo,
+}
+
+// Ctrl+K, type to filter, arrows to pick, enter to run, escape to close
+pub struct Palette {
+    pub open: bool,
+    query: String,
+    selected: usize,
+}
+
+impl Palette {
+    pub fn new() -> Self {
+        Self { open: false, query: String::new(), selected: 0 }
+    }
+
+    pub fn toggle(&mut self) {
+        self.open = !self.open;
+        self.query.clear();
+        self.selected = 0;
+    }
+
+    // `commands` is everything available right now, a number typed in the
+    // query offers "Set BPM" on top
+    pub fn ui(&mut self, ctx: &egui::Context, mut commands: Vec<(String, Command)>) -> Option<Command> {
+        if let Ok(bpm) = self.query.trim().parse::<i32>() {
+            commands.insert(0, (format!("Set BPM {}", bpm), Command::SetBpm(bpm)));
+        }
+        let query = self.query.to_lowercase();
+        let matches: Vec<(String, Command)> = commands
+            .into_iter()
+            .filter(|(name, command)| {
+                let name = name.to_lowercase();
+                matches!(command, Command::SetBpm(_)) || query.split_whitespace().all(|w| name.contains(w))
+            })
+            .collect();
+        self.selected = self.selected.min(matches.len().saturating_sub(1));
+
+        let (down, up, enter) = ctx.input(|i| {
+            (
+                i.key_pressed(egui::Key::ArrowDown),
+                i.key_pressed(egui::Key::ArrowUp),
+                i.key_pressed(egui::Key::Enter),
+            )
+        });
+        if down && self.selected + 1 < matches.len() {
+            self.selected += 1;
+        }
+        if up && self.selected > 0 {
+            self.selected -= 1;
+        }
+
+        let mut run = None;
+        let modal = egui::Modal::new(egui::Id::new("palette")).show(ctx, |ui| {
+            ui.set_width(300.0);
+            let edit = ui.add(egui::TextEdit::singleline(&mut self.query).hint_text("Type a command or a BPM"));
+            edit.request_focus();
+            egui::ScrollArea::vertical().max_height(220.0).show(ui, |ui| {
+                for (index, (name, command)) in matches.iter().enumerate() {
+                    let label = ui.selectable_label(index == self.selected, name.as_str());
+                    if index == self.selected && (up || down) {
+                        label.scroll_to_me(None);
+                    }
+                    if label.clicked() {
+                        run = Some(*command);
+                    }
+                }
+            });
+        });
+        if enter {
+            run = run.or(matches.get(self.selected).map(|(_, c)| *c));
+        }
+        if run.is_some() || modal.should_close() {
+            self.toggle();
+        }
+        run
+    }
+}
@@ -0,0 +1,197 @@
+use std::ffi::c_void;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use crate::clock::{Clock, Request};
+
+pub const NAME: &str = r"\\.\pipe\midiclock";
+
+// line based control for scripts and overlays on the same machine, one reply line
+// per command:
+//   tabs | tab <n> | bpm | bpm <n> | start | stop | state | ports | port <n> on|off
+// commands go to tab 0 until another is picked, tabs and ports are counted from 0
+pub fn serve(clocks: Arc<Mutex<Vec<Arc<Clock>>>>, ports: Vec<String>) {
+    let ports = Arc::new(ports);
+    let spawned = thread::Builder::new().name("pipe".to_string()).spawn(move || {
+        loop {
+            let pipe = match Pipe::listen() {
+                Ok(pipe) => pipe,
+                Err(e) => {
+                    eprintln!("Failed to open {}: {}", NAME, e);
+                    return;
+                }
+            };
+            let clocks = Arc::clone(&clocks);
+            let ports = Arc::clone(&ports);
+            // each client gets its own thread, the next instance is up right away
+            thread::spawn(move || client(pipe, &clocks, &ports));
+        }
+    });
+    if let Err(e) = spawned {
+        eprintln!("Failed to start pipe thread: {}", e);
+    }
+}
+
+fn client(pipe: Pipe, clocks: &Mutex<Vec<Arc<Clock>>>, ports: &[String]) {
+    let mut tab = 0;
+    let mut reader = BufReader::new(pipe);
+    let mut line = String::new();
+    while reader.read_line(&mut line).is_ok_and(|n| n > 0) {
+        let reply = match command(line.trim(), &mut tab, clocks, ports) {
+            Ok(reply) => reply,
+            Err(e) => format!("error: {}", e),
+        };
+        line.clear();
+        if writeln!(reader.get_mut(), "{}", reply).is_err() {
+            return;
+        }
+    }
+}
+
+fn command(line: &str, tab: &mut usize, clocks: &Mutex<Vec<Arc<Clock>>>, ports: &[String]) -> Result<String, String> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let clocks = clocks.lock().unwrap();
+    if let ["tabs"] = words[..] {
+        return Ok(clocks.iter().map(|c| c.name.as_str()).collect::<Vec<_>>().join("; "));
+    }
+    if let ["tab", n] = words[..] {
+        *tab = n.parse().ok().filter(|&n| n < clocks.len()).ok_or(format!("No tab {}", n))?;
+        return Ok("ok".to_string());
+    }
+    let clock = clocks.get(*tab).ok_or(format!("No tab {}", tab))?;
+    match words[..] {
+        ["bpm"] => Ok(format!("{:.2}", clock.tempo())),
+        ["bpm", bpm] => {
+            let bpm = bpm.parse().ok().filter(|b| (1..=999).contains(b)).ok_or(format!("Not a tempo: {}", bpm))?;
+            clock.request(Request::Tempo(bpm));
+            Ok("ok".to_string())
+        }
+        ["start"] => {
+            clock.request(Request::Start(0));
+            Ok("ok".to_string())
+        }
+        ["stop"] => {
+            clock.request(Request::Stop);
+            Ok("ok".to_string())
+        }
+        ["state"] => Ok(if clock.running.load(Ordering::SeqCst) { "running" } else { "stopped" }.to_string()),
+        ["ports"] => Ok(ports
+            .iter()
+            .enumerate()
+            .skip(1)
+            .map(|(i, name)| {
+                let on = clock.outputs[i].enabled.load(Ordering::SeqCst);
+                format!("{}={}{}", i, name, if on { " (on)" } else { "" })
+            })
+            .collect::<Vec<_>>()
+            .join("; ")),
+        ["port", n, on @ ("on" | "off")] => {
+            let output = n.parse::<usize>().ok().filter(|&n| n > 0).and_then(|n| clock.outputs.get(n)).ok_or(format!("No port {}", n))?;
+            output.enabled.store(on == "on", Ordering::SeqCst);
+            clock.poke();
+            Ok("ok".to_string())
+        }
+        _ => Err(format!("Unknown command: {}", line)),
+    }
+}
+
+const PIPE_ACCESS_DUPLEX: u32 = 0x3;
+const PIPE_REJECT_REMOTE_CLIENTS: u32 = 0x8;
+const PIPE_UNLIMITED_INSTANCES: u32 = 255;
+const INVALID_HANDLE_VALUE: isize = -1;
+const ERROR_PIPE_CONNECTED: i32 = 535;
+
+// one connected instance of the pipe, closed on drop
+struct Pipe(isize);
+
+impl Pipe {
+    // waits for a client
+    fn listen() -> io::Result<Self> {
+        let name: Vec<u16> = NAME.encode_utf16().chain([0]).collect();
+        // SAFETY: the name is NUL terminated and outlives the call, no security attributes
+        let handle = unsafe {
+            CreateNamedPipeW(
+                name.as_ptr(),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                std::ptr::null_mut(),
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(io::Error::last_os_error());
+        }
+        let pipe = Pipe(handle);
+        // SAFETY: a valid pipe handle, no overlapped io
+        if unsafe { ConnectNamedPipe(pipe.0, std::ptr::null_mut()) } == 0 {
+            // a client that got in between creating and waiting is fine
+            let e = io::Error::last_os_error();
+            if e.raw_os_error() != Some(ERROR_PIPE_CONNECTED) {
+                return Err(e);
+            }
+        }
+        Ok(pipe)
+    }
+}
+
+impl Read for Pipe {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut read = 0;
+        // SAFETY: the buffer is valid for its length, no overlapped io
+        if unsafe { ReadFile(self.0, buf.as_mut_ptr(), buf.len() as u32, &mut read, std::ptr::null_mut()) } == 0 {
+            // the client hanging up ends the stream
+            return Ok(0);
+        }
+        Ok(read as usize)
+    }
+}
+
+impl Write for Pipe {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let mut written = 0;
+        // SAFETY: as above
+        if unsafe { WriteFile(self.0, buf.as_ptr(), buf.len() as u32, &mut written, std::ptr::null_mut()) } == 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(written as usize)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for Pipe {
+    fn drop(&mut self) {
+        // SAFETY: our handle, closed only here
+        unsafe {
+            FlushFileBuffers(self.0);
+            DisconnectNamedPipe(self.0);
+            CloseHandle(self.0);
+        }
+    }
+}
+
+#[link(name = "kernel32")]
+unsafe extern "system" {
+    fn CreateNamedPipeW(
+        name: *const u16,
+        open_mode: u32,
+        pipe_mode: u32,
+        max_instances: u32,
+        out_buffer: u32,
+        in_buffer: u32,
+        timeout: u32,
+        security: *mut c_void,
+    ) -> isize;
+    fn ConnectNamedPipe(pipe: isize, overlapped: *mut c_void) -> i32;
+    fn ReadFile(file: isize, buffer: *mut u8, len: u32, read: *mut u32, overlapped: *mut c_void) -> i32;
+    fn WriteFile(file: isize, buffer: *const u8, len: u32, written: *mut u32, overlapped: *mut c_void) -> i32;
+    fn FlushFileBuffers(file: isize) -> i32;
+    fn DisconnectNamedPipe(pipe: isize) -> i32;
+    fn CloseHandle(handle: isize) -> i32;
+}
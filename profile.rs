@@ -0,0 +1,141 @@
+use std::fs;
+use std::sync::atomic::Ordering;
+use crate::clock::Output;
+
+pub const PPQN: [u32; 3] = [24, 48, 96];
+
+// what a device needs that the MIDI spec doesn't promise, kept by port name
+#[derive(Clone, PartialEq)]
+pub struct Profile {
+    // clock only while the transport runs
+    pub gate: bool,
+    // beats of clock before Start so it can lock on, for gated devices
+    pub preroll: u32,
+    // clocks per quarter note it counts, 24 is the spec
+    pub ppqn: u32,
+    // hex bytes or a .syx file, sent when the port opens and before Start
+    pub sysex: String,
+    // sent with the BPM filled in whenever the tempo changes, see `Template`
+    pub tempo_sysex: String,
+}
+
+impl Profile {
+    pub fn new() -> Self {
+        Self { gate: false, preroll: 0, ppqn: 24, sysex: String::new(), tempo_sysex: String::new() }
+    }
+
+    pub fn apply(&self, output: &Output) {
+        output.gate.store(self.gate, Ordering::SeqCst);
+        output.preroll.store(self.preroll, Ordering::SeqCst);
+        output.ppqn.store(self.ppqn, Ordering::SeqCst);
+        *output.sysex.lock().unwrap() = sysex(&self.sysex).unwrap_or_default();
+        *output.tempo_sysex.lock().unwrap() = Template::parse(&self.tempo_sysex).ok().flatten();
+    }
+
+    // `ppqn;preroll;gate;sysex;tempo sysex;port name`, the name last as it may hold anything
+    pub fn to_config(&self, port: &str) -> String {
+        let sysex = self.sysex.replace(';', "");
+        let tempo_sysex = self.tempo_sysex.replace(';', "");
+        format!("{};{};{};{};{};{}", self.ppqn, self.preroll, self.gate as u8, sysex, tempo_sysex, port)
+    }
+
+    pub fn from_config(value: &str) -> Option<(String, Self)> {
+        let mut fields = value.splitn(6, ';');
+        let ppqn = fields.next()?.parse().ok().filter(|p| PPQN.contains(p))?;
+        let preroll = fields.next()?.parse().ok()?;
+        let gate = fields.next()? == "1";
+        let sysex = fields.next()?.to_string();
+        let tempo_sysex = fields.next()?.to_string();
+        Some((fields.next()?.to_string(), Self { gate, preroll, ppqn, sysex, tempo_sysex }))
+    }
+}
+
+// the messages in typed hex like `F0 7E 7F 06 01 F7` or in a .syx file, which may
+// hold several back to back
+pub fn sysex(text: &str) -> Result<Vec<Vec<u8>>, String> {
+    let text = text.trim();
+    let bytes = if text.to_lowercase().ends_with(".syx") {
+        fs::read(text).map_err(|e| format!("Failed to read {}: {}", text, e))?
+    } else {
+        let hex: String = text.split_whitespace().collect();
+        if !hex.is_ascii() || !hex.len().is_multiple_of(2) {
+            return Err("Use pairs of hex digits".to_string());
+        }
+        (0..hex.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).map_err(|_| format!("Not hex: {}", &hex[i..i + 2])))
+            .collect::<Result<_, _>>()?
+    };
+    let mut messages = Vec::new();
+    let mut rest = &bytes[..];
+    while !rest.is_empty() {
+        let Some(end) = rest.iter().position(|&b| b == 0xF7) else {
+            return Err("SysEx without an F7 at the end".to_string());
+        };
+        let (message, tail) = rest.split_at(end + 1);
+        if message[0] != 0xF0 || message[1..end].iter().any(|&b| b & 0x80 != 0) {
+            return Err("SysEx has to start with F0 and hold only 7-bit data".to_string());
+        }
+        messages.push(message.to_vec());
+        rest = tail;
+    }
+    Ok(messages)
+}
+
+enum Piece {
+    Byte(u8),
+    // the BPM times `scale` as `bytes` 7-bit bytes, most significant first
+    Bpm { scale: f64, bytes: u32 },
+}
+
+// a SysEx message with the tempo in it, typed as hex bytes and placeholders like
+// `F0 43 10 {bpm10:2} F7`: {bpm:N}, {bpm10:N} and {bpm100:N} put the whole BPM,
+// tenths or hundredths into N bytes
+pub struct Template(Vec<Piece>);
+
+impl Template {
+    // None for an empty template
+    pub fn parse(text: &str) -> Result<Option<Self>, String> {
+        let mut pieces = Vec::new();
+        for token in text.split_whitespace() {
+            let piece = match token.strip_prefix('{').and_then(|t| t.strip_suffix('}')) {
+                Some(inner) => {
+                    let (name, bytes) = inner.split_once(':').unwrap_or((inner, "2"));
+                    let scale = match name {
+                        "bpm" => 1.0,
+                        "bpm10" => 10.0,
+                        "bpm100" => 100.0,
+                        _ => return Err(format!("Unknown placeholder {}", token)),
+                    };
+                    let bytes = bytes.parse().ok().filter(|b| (1..=4).contains(b)).ok_or(format!("{} takes 1 to 4 bytes", name))?;
+                    Piece::Bpm { scale, bytes }
+                }
+                None => Piece::Byte(u8::from_str_radix(token, 16).map_err(|_| format!("Not a hex byte: {}", token))?),
+            };
+            pieces.push(piece);
+        }
+        match (pieces.first(), pieces.last()) {
+            (None, _) => return Ok(None),
+            (Some(Piece::Byte(0xF0)), Some(Piece::Byte(0xF7))) => {}
+            _ => return Err("SysEx has to start with F0 and end with F7".to_string()),
+        }
+        if pieces[1..pieces.len() - 1].iter().any(|p| matches!(p, Piece::Byte(b) if b & 0x80 != 0)) {
+            return Err("SysEx can only hold 7-bit data".to_string());
+        }
+        Ok(Some(Self(pieces)))
+    }
+
+    pub fn fill(&self, bpm: f64) -> Vec<u8> {
+        let mut msg = Vec::new();
+        for piece in &self.0 {
+            match *piece {
+                Piece::Byte(b) => msg.push(b),
+                Piece::Bpm { scale, bytes } => {
+                    let value = ((bpm * scale).round() as u32).min((1 << (7 * bytes)) - 1);
+                    msg.extend((0..bytes).rev().map(|i| ((value >> (7 * i)) & 0x7F) as u8));
+                }
+            }
+        }
+        msg
+    }
+}
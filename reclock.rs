@@ -0,0 +1,155 @@
+use std::time::{Duration, Instant};
+
+// gains of the phase and period correction once locked
+const ALPHA: f64 = 0.05;
+const BETA: f64 = 0.002;
+// ticks to average before trusting the estimate (one beat)
+const LOCK_TICKS: u32 = 24;
+// no tick for this long means the master stopped
+const TIMEOUT: f64 = 0.5;
+// a gap this close to a whole number of periods is dropped ticks, at most this many
+const SLACK: f64 = 0.15;
+const MAX_DROPPED: f64 = 3.0;
+// this many odd gaps in a row are the master changing tempo
+const ODD_RUN: u32 = 3;
+
+// second order PLL over the incoming clock: `anchor` is the smoothed time of
+// the last incoming tick, `period` the smoothed tick interval
+pub struct Pll {
+    anchor: Option<Instant>,
+    period: f64,
+    ticks: u32,
+    // gaps in a row that weren't one period
+    odd: u32,
+}
+
+impl Pll {
+    pub fn new() -> Self {
+        Self { anchor: None, period: 0.0, ticks: 0, odd: 0 }
+    }
+
+    pub fn tick(&mut self, now: Instant) {
+        let Some(anchor) = self.anchor else {
+            self.anchor = Some(now);
+            return;
+        };
+        let dt = secs_between(anchor, now);
+        if dt > TIMEOUT || dt <= 0.0 {
+            *self = Self::new();
+            self.anchor = Some(now);
+            return;
+        }
+
+        if self.ticks < LOCK_TICKS {
+            // plain running average until we have a beat worth of ticks
+            self.period = (self.period * self.ticks as f64 + dt) / (self.ticks + 1) as f64;
+            self.anchor = Some(now);
+        } else {
+            // a dropped tick shows up as a double gap, don't let it bend the period. gaps
+            // that don't fit, or double ones that keep coming, are a new tempo: average again
+            let n = (dt / self.period).round().max(1.0);
+            let err = dt - n * self.period;
+            let fits = err.abs() < SLACK * self.period && n <= MAX_DROPPED + 1.0;
+            self.odd = if fits && n == 1.0 { 0 } else { self.odd + 1 };
+            if self.odd >= ODD_RUN {
+                *self = Self::new();
+                self.anchor = Some(now);
+                self.period = dt;
+                self.ticks = 1;
+                return;
+            }
+            if fits {
+                self.anchor = Some(shift(anchor, n * self.period + ALPHA * err));
+                self.period += BETA * err / n;
+            } else {
+                self.anchor = Some(now);
+            }
+        }
+        self.ticks = self.ticks.saturating_add(1);
+    }
+
+    fn locked(&self, now: Instant) -> bool {
+        self.ticks >= LOCK_TICKS
+            && self.anchor.is_some_and(|a| secs_between(a, now) < TIMEOUT)
+    }
+
+    pub fn lock_point(&self, now: Instant) -> Option<(Instant, f64)> {
+        if !self.locked(now) {
+            return None;
+        }
+        self.anchor.map(|a| (a, self.period))
+    }
+
+    pub fn bpm(&self, now: Instant) -> Option<f64> {
+        self.lock_point(now).map(|(_, period)| 60.0 / (period * 24.0))
+    }
+}
+
+// move a scheduled tick onto the nearest point of the smoothed incoming grid
+pub fn snap(next: Instant, anchor: Instant, period: f64, now: Instant) -> Instant {
+    // after an idle spell don't try to catch up on missed ticks
+    let next = if secs_between(next, now) > period { now } else { next };
+    let k = (secs_between(anchor, next) / period).round();
+    shift(anchor, k * period)
+}
+
+// signed seconds from a to b
+pub fn secs_between(a: Instant, b: Instant) -> f64 {
+    if b >= a {
+        (b - a).as_secs_f64()
+    } else {
+        -(a - b).as_secs_f64()
+    }
+}
+
+pub fn shift(t: Instant, secs: f64) -> Instant {
+    if secs >= 0.0 {
+        t + Duration::from_secs_f64(secs)
+    } else {
+        t - Duration::from_secs_f64(-secs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `bpm` for `beats`, starting at `t`, returns where it left off
+    fn feed(pll: &mut Pll, t: Instant, bpm: f64, beats: u32) -> Instant {
+        let period = 60.0 / (bpm * 24.0);
+        let mut t = t;
+        for _ in 0..beats * 24 {
+            t = shift(t, period);
+            pll.tick(t);
+        }
+        t
+    }
+
+    #[test]
+    fn dropped_tick() {
+        let mut pll = Pll::new();
+        let t = feed(&mut pll, Instant::now(), 120.0, 2);
+        let t = shift(t, 2.0 * 60.0 / (120.0 * 24.0));
+        pll.tick(t);
+        let t = feed(&mut pll, t, 120.0, 1);
+        assert!((pll.bpm(t).unwrap() - 120.0).abs() < 0.01);
+    }
+
+    #[test]
+    fn halved_tempo() {
+        let mut pll = Pll::new();
+        let t = feed(&mut pll, Instant::now(), 120.0, 2);
+        let t = feed(&mut pll, t, 60.0, 2);
+        let bpm = pll.bpm(t).unwrap();
+        assert!((bpm - 60.0).abs() < 0.01, "following at {} BPM", bpm);
+    }
+
+    #[test]
+    fn slower_tempo() {
+        let mut pll = Pll::new();
+        let t = feed(&mut pll, Instant::now(), 120.0, 2);
+        let t = feed(&mut pll, t, 80.0, 2);
+        let bpm = pll.bpm(t).unwrap();
+        assert!((bpm - 80.0).abs() < 0.01, "following at {} BPM", bpm);
+    }
+}
@@ -0,0 +1,53 @@
+use std::time::{Duration, Instant};
+use eframe::egui;
+
+// before a held key starts repeating
+const DELAY: Duration = Duration::from_millis(400);
+// seconds between repeats at first, and once held for RAMP past the delay
+const SLOW: f64 = 0.15;
+const FAST: f64 = 0.02;
+const RAMP: f64 = 2.0;
+// a stalled frame shouldn't jump the tempo
+const MAX_STEPS: u32 = 10;
+
+// our own auto-repeat for held keys, ignoring the system's: a step on the press,
+// then steps that come faster the longer it's held
+pub struct Repeat {
+    held: Option<(egui::Key, Instant)>,
+    next: Instant,
+}
+
+impl Repeat {
+    pub fn new() -> Self {
+        Self { held: None, next: Instant::now() }
+    }
+
+    // how many steps the key down now has earned since the last call
+    pub fn steps(&mut self, down: Option<egui::Key>, now: Instant) -> u32 {
+        let Some(key) = down else {
+            self.held = None;
+            return 0;
+        };
+        let pressed = match self.held {
+            Some((held, pressed)) if held == key => pressed,
+            _ => {
+                self.held = Some((key, now));
+                self.next = now + DELAY;
+                return 1;
+            }
+        };
+        let mut steps = 0;
+        while self.next <= now && steps < MAX_STEPS {
+            let ramp = ((self.next - pressed - DELAY).as_secs_f64() / RAMP).min(1.0);
+            self.next += Duration::from_secs_f64(SLOW + (FAST - SLOW) * ramp);
+            steps += 1;
+        }
+        self.next = self.next.max(now);
+        steps
+    }
+
+    // when to look again while a key is held
+    pub fn wait(&self, now: Instant) -> Option<Duration> {
+        self.held.map(|_| self.next.saturating_duration_since(now))
+    }
+}
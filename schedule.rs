@@ -0,0 +1,104 @@
+use std::time::SystemTime;
+use chrono::{DateTime, Local, NaiveTime, TimeDelta, TimeZone};
+
+// "20:00" or "20:00:00", as seconds into the day
+pub fn parse(text: &str) -> Option<u64> {
+    let mut fields = text.trim().split(':').map(|f| f.trim().parse::<u64>().ok());
+    let h = fields.next()??;
+    let m = fields.next()??;
+    let s = fields.next().unwrap_or(Some(0))?;
+    if fields.next().is_some() || h > 23 || m > 59 || s > 59 {
+        return None;
+    }
+    Some(h * 3600 + m * 60 + s)
+}
+
+// the next time the local clock reads `secs`, in the system's time zone. a time the
+// clocks skip going forward comes an hour later, one they pass twice the first time
+pub fn next(secs: u64, now: SystemTime) -> SystemTime {
+    let now = DateTime::<Local>::from(now);
+    let Some(time) = NaiveTime::from_num_seconds_from_midnight_opt(secs as u32, 0) else {
+        return now.into();
+    };
+    let mut date = now.date_naive();
+    loop {
+        let local = date.and_time(time);
+        let at = Local
+            .from_local_datetime(&local)
+            .earliest()
+            .or_else(|| Local.from_local_datetime(&(local + TimeDelta::hours(1))).earliest());
+        if let Some(at) = at
+            && at > now
+        {
+            return at.into();
+        }
+        let Some(tomorrow) = date.succ_opt() else {
+            return now.into();
+        };
+        date = tomorrow;
+    }
+}
+
+// "HH:MM:SS" on the local clock
+pub fn clock_time(t: SystemTime) -> String {
+    DateTime::<Local>::from(t).format("%H:%M:%S").to_string()
+}
+
+// "1:02:03" or "2:03" until `t`
+pub fn countdown(t: SystemTime) -> String {
+    let secs = t.duration_since(SystemTime::now()).unwrap_or_default().as_secs_f64().ceil() as u64;
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Action {
+    Start,
+    Stop,
+    Bpm(i32),
+}
+
+impl Action {
+    pub fn name(self) -> &'static str {
+        match self {
+            Action::Start => "Start",
+            Action::Stop => "Stop",
+            Action::Bpm(_) => "Tempo",
+        }
+    }
+}
+
+// "at 20:15 change to 98 BPM", once or every day
+pub struct Timer {
+    // as typed
+    pub time: String,
+    pub action: Action,
+    pub daily: bool,
+    // when it next goes off, None once done or while the time doesn't parse
+    pub at: Option<SystemTime>,
+}
+
+impl Timer {
+    pub fn new(time: String, action: Action) -> Self {
+        let mut timer = Self { time, action, daily: false, at: None };
+        timer.schedule();
+        timer
+    }
+
+    // after editing the time
+    pub fn schedule(&mut self) {
+        self.at = parse(&self.time).map(|secs| next(secs, SystemTime::now()));
+    }
+
+    // gone off at `at`, a daily one comes round again at the same local time,
+    // whatever daylight saving did overnight
+    pub fn done(&mut self, now: SystemTime) {
+        self.at = match self.daily {
+            true => parse(&self.time).map(|secs| next(secs, now)),
+            false => None,
+        };
+    }
+}
@@ -0,0 +1,129 @@
+use std::collections::VecDeque;
+use std::fs;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+use crate::config;
+
+// plenty for a long show, the oldest go first
+const MAX_EVENTS: usize = 100_000;
+
+static EVENTS: Mutex<VecDeque<Event>> = Mutex::new(VecDeque::new());
+
+#[derive(Clone, Copy)]
+pub enum Kind {
+    Tempo,
+    Transport,
+    Port,
+    Error,
+}
+
+impl Kind {
+    fn name(self) -> &'static str {
+        match self {
+            Kind::Tempo => "tempo",
+            Kind::Transport => "transport",
+            Kind::Port => "port",
+            Kind::Error => "error",
+        }
+    }
+}
+
+struct Event {
+    at: SystemTime,
+    // tab name, empty for app wide things
+    source: String,
+    kind: Kind,
+    text: String,
+}
+
+// anything worth reading after the show, from any thread
+pub fn record(source: &str, kind: Kind, text: String) {
+    let mut events = EVENTS.lock().unwrap();
+    if events.len() >= MAX_EVENTS {
+        events.pop_front();
+    }
+    events.push_back(Event { at: SystemTime::now(), source: source.to_string(), kind, text });
+}
+
+pub fn len() -> usize {
+    EVENTS.lock().unwrap().len()
+}
+
+// writes midiclock-session-<time>.json or .csv where exports go, returns the file name
+pub fn export(json: bool) -> Result<String, String> {
+    let events = EVENTS.lock().unwrap();
+    let text = if json {
+        let rows: Vec<String> = events
+            .iter()
+            .map(|e| {
+                format!(
+                    "  {{\"time\": \"{}\", \"clock\": \"{}\", \"kind\": \"{}\", \"text\": \"{}\"}}",
+                    timestamp(e.at),
+                    json_escape(&e.source),
+                    e.kind.name(),
+                    json_escape(&e.text),
+                )
+            })
+            .collect();
+        format!("[\n{}\n]\n", rows.join(",\n"))
+    } else {
+        let mut text = "time,clock,kind,text\n".to_string();
+        for e in events.iter() {
+            text += &format!("{},{},{},{}\n", timestamp(e.at), csv_field(&e.source), e.kind.name(), csv_field(&e.text));
+        }
+        text
+    };
+    let stamp = timestamp(SystemTime::now()).replace([':', '.'], "-");
+    let name = format!("midiclock-session-{}.{}", stamp, if json { "json" } else { "csv" });
+    fs::write(config::output(&name), text).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+    Ok(name)
+}
+
+// ISO 8601 in UTC with milliseconds, without pulling in a date crate
+pub fn timestamp(t: SystemTime) -> String {
+    let since = t.duration_since(UNIX_EPOCH).unwrap_or_default();
+    let secs = since.as_secs();
+    let (days, rest) = (secs / 86400, secs % 86400);
+    // civil from days, Howard Hinnant's algorithm
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:03}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest % 3600 / 60,
+        rest % 60,
+        since.subsec_millis(),
+    )
+}
+
+fn json_escape(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '"' => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_string()
+    }
+}
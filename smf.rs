@@ -0,0 +1,99 @@
+use std::fs;
+use std::path::Path;
+
+// a Standard MIDI File's tempo changes as (beat, bpm), in order. a file without any
+// plays at the spec's default of 120
+pub fn tempo_map(path: &Path) -> Result<Vec<(f64, f64)>, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if data.len() < 14 || &data[0..4] != b"MThd" {
+        return Err("Not a MIDI file".to_string());
+    }
+    let division = u16::from_be_bytes([data[12], data[13]]);
+    let header = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as usize;
+
+    let mut changes = Vec::new();
+    let mut at = 8 + header;
+    while at + 8 <= data.len() {
+        let len = u32::from_be_bytes([data[at + 4], data[at + 5], data[at + 6], data[at + 7]]) as usize;
+        let body = &data[at + 8..(at + 8 + len).min(data.len())];
+        if &data[at..at + 4] == b"MTrk" {
+            track(body, &mut changes);
+        }
+        at += 8 + len;
+    }
+    changes.sort_by_key(|&(tick, _)| tick);
+
+    // SMPTE timing counts frames, not beats, so only the opening tempo means anything
+    if division & 0x8000 != 0 {
+        changes.truncate(1);
+    }
+    let ppq = division.max(1) as f64;
+    let mut map: Vec<(f64, f64)> = Vec::new();
+    for (tick, bpm) in changes {
+        let beat = if division & 0x8000 != 0 { 0.0 } else { tick as f64 / ppq };
+        match map.last_mut() {
+            // several tracks can repeat the same change, or replace it on the same tick
+            Some(last) if last.0 == beat => last.1 = bpm,
+            Some(last) if last.1 == bpm => {}
+            _ => map.push((beat, bpm)),
+        }
+    }
+    if map.first().is_none_or(|&(beat, _)| beat > 0.0) {
+        map.insert(0, (0.0, 120.0));
+    }
+    Ok(map)
+}
+
+// tempo meta events in one track, with their absolute tick
+fn track(body: &[u8], changes: &mut Vec<(u64, f64)>) {
+    let mut i = 0;
+    let mut tick = 0;
+    let mut status = 0;
+    while i < body.len() {
+        let Some(delta) = varlen(body, &mut i) else { return };
+        tick += delta;
+        let Some(&byte) = body.get(i) else { return };
+        if byte & 0x80 != 0 {
+            status = byte;
+            i += 1;
+        }
+        match status {
+            0xFF => {
+                let Some(&kind) = body.get(i) else { return };
+                i += 1;
+                let Some(len) = varlen(body, &mut i) else { return };
+                let len = len as usize;
+                if kind == 0x51 && len == 3 && i + 3 <= body.len() {
+                    let micros = u32::from_be_bytes([0, body[i], body[i + 1], body[i + 2]]);
+                    if micros > 0 {
+                        changes.push((tick, 60_000_000.0 / micros as f64));
+                    }
+                }
+                i += len;
+                // meta and sysex don't set running status
+                status = 0;
+            }
+            0xF0 | 0xF7 => {
+                let Some(len) = varlen(body, &mut i) else { return };
+                i += len as usize;
+                status = 0;
+            }
+            0x80..=0xEF => i += if matches!(status & 0xF0, 0xC0 | 0xD0) { 1 } else { 2 },
+            // data without a status to run on, the track is broken
+            _ => return,
+        }
+    }
+}
+
+fn varlen(body: &[u8], i: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for _ in 0..4 {
+        let byte = *body.get(*i)?;
+        *i += 1;
+        value = (value << 7) | (byte & 0x7F) as u64;
+        if byte & 0x80 == 0 {
+            return Some(value);
+        }
+    }
+    None
+}
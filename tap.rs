@@ -0,0 +1,161 @@
+use std::time::Instant;
+
+// the note value being tapped, converted back to quarter note BPM
+#[derive(Clone, Copy, PartialEq)]
+pub enum Subdivision {
+    Half,
+    DottedQuarter,
+    Quarter,
+    Eighth,
+}
+
+impl Subdivision {
+    pub const ALL: [Subdivision; 4] = [
+        Subdivision::Half,
+        Subdivision::DottedQuarter,
+        Subdivision::Quarter,
+        Subdivision::Eighth,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Subdivision::Half => "Halves",
+            Subdivision::DottedQuarter => "Dotted quarters",
+            Subdivision::Quarter => "Quarters",
+            Subdivision::Eighth => "Eighths",
+        }
+    }
+
+    // quarter notes between two taps
+    pub fn beats(self) -> f64 {
+        match self {
+            Subdivision::Half => 2.0,
+            Subdivision::DottedQuarter => 1.5,
+            Subdivision::Quarter => 1.0,
+            Subdivision::Eighth => 0.5,
+        }
+    }
+
+    pub fn bpm(self, interval_secs: f64) -> f64 {
+        60.0 * self.beats() / interval_secs
+    }
+}
+
+// how precise a tapped tempo is kept
+#[derive(Clone, Copy, PartialEq)]
+pub enum Rounding {
+    Whole,
+    Half,
+    Hundredth,
+}
+
+impl Rounding {
+    pub const ALL: [Rounding; 3] = [Rounding::Whole, Rounding::Half, Rounding::Hundredth];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Rounding::Whole => "Whole BPM",
+            Rounding::Half => "Half BPM",
+            Rounding::Hundredth => "Unrounded",
+        }
+    }
+
+    pub fn apply(self, bpm: f64) -> f64 {
+        let steps = match self {
+            Rounding::Whole => 1.0,
+            Rounding::Half => 2.0,
+            Rounding::Hundredth => 100.0,
+        };
+        (bpm * steps).round() / steps
+    }
+}
+
+// intervals averaged into the tempo
+const WINDOW: usize = 8;
+// further off the median than this is a fumbled tap
+const OUTLIER: f64 = 0.2;
+// how close an interval has to be to twice or half the median to count as a switch
+const OCTAVE: f64 = 0.1;
+// a pause longer than this starts over, unless set otherwise
+pub const RESTART: f64 = 3.0;
+// taps in a row on the same tempo that put a previewed one on the clock
+pub const STABLE: u32 = 4;
+
+// keeps the last few tap intervals, throwing out fumbles and folding
+// half and double time taps back onto the running tempo
+pub struct Tapper {
+    last: Option<Instant>,
+    intervals: Vec<f64>,
+    // the last rejected interval, two that agree are a new tempo rather than fumbles
+    rejected: Option<f64>,
+}
+
+impl Tapper {
+    pub fn new() -> Self {
+        Self { last: None, intervals: Vec::new(), rejected: None }
+    }
+
+    // returns the averaged seconds per tap once there's an interval. a tap more than
+    // `restart` seconds after the last one begins a new run
+    pub fn tap(&mut self, now: Instant, restart: f64) -> Option<f64> {
+        let last = self.last.replace(now)?;
+        let d = now.duration_since(last).as_secs_f64();
+        if d <= 0.0 {
+            return self.average();
+        }
+        if d > restart {
+            self.reset();
+            self.last = Some(now);
+            return None;
+        }
+        let Some(median) = self.median() else {
+            self.intervals.push(d);
+            return self.average();
+        };
+        let ratio = d / median;
+        if (ratio - 1.0).abs() <= OUTLIER {
+            self.accept(d);
+        } else if (ratio - 2.0).abs() <= 2.0 * OCTAVE {
+            self.accept(d / 2.0);
+        } else if (ratio - 0.5).abs() <= 0.5 * OCTAVE {
+            self.accept(d * 2.0);
+        } else {
+            match self.rejected {
+                Some(r) if ((d - r) / r).abs() <= OUTLIER => {
+                    self.intervals = vec![r, d];
+                    self.rejected = None;
+                }
+                _ => self.rejected = Some(d),
+            }
+        }
+        self.average()
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn accept(&mut self, d: f64) {
+        self.rejected = None;
+        self.intervals.push(d);
+        if self.intervals.len() > WINDOW {
+            self.intervals.remove(0);
+        }
+    }
+
+    fn median(&self) -> Option<f64> {
+        if self.intervals.is_empty() {
+            return None;
+        }
+        let mut sorted = self.intervals.clone();
+        sorted.sort_by(f64::total_cmp);
+        Some(sorted[sorted.len() / 2])
+    }
+
+    fn average(&self) -> Option<f64> {
+        if self.intervals.is_empty() {
+            return None;
+        }
+        Some(self.intervals.iter().sum::<f64>() / self.intervals.len() as f64)
+    }
+}
@@ -0,0 +1,75 @@
+use std::sync::atomic::{AtomicBool, AtomicI32, AtomicU32, AtomicU64, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// deliberately degrades the output, for testing how slaves cope with a bad master
+pub struct Humanize {
+    // max timing error of a single tick, doesn't accumulate
+    pub jitter_us: AtomicU32,
+    // how far the tempo may random walk away from the set one
+    pub wander_ppm: AtomicU32,
+    // how fast the tempo creeps steadily away from the set one, per minute of
+    // running, positive for faster
+    pub drift_ppm: AtomicI32,
+    // how far it has crept so far, f64 ppm, and a request to start over
+    pub drifted: AtomicU64,
+    pub drift_reset: AtomicBool,
+}
+
+impl Humanize {
+    pub fn new() -> Self {
+        Self {
+            jitter_us: AtomicU32::new(0),
+            wander_ppm: AtomicU32::new(0),
+            drift_ppm: AtomicI32::new(0),
+            drifted: AtomicU64::new(0f64.to_bits()),
+            drift_reset: AtomicBool::new(false),
+        }
+    }
+
+    pub fn jitter(&self) -> f64 {
+        self.jitter_us.load(Ordering::SeqCst) as f64 / 1_000_000.0
+    }
+
+    pub fn wander(&self) -> f64 {
+        self.wander_ppm.load(Ordering::SeqCst) as f64 / 1_000_000.0
+    }
+
+    // as a fraction of the tempo per second
+    pub fn drift(&self) -> f64 {
+        self.drift_ppm.load(Ordering::SeqCst) as f64 / 1_000_000.0 / 60.0
+    }
+
+    pub fn drifted_ppm(&self) -> f64 {
+        f64::from_bits(self.drifted.load(Ordering::SeqCst))
+    }
+}
+
+// cents are a ratio of 2^(1/1200), so for small amounts about 578 ppm each
+pub fn cents_to_ppm(cents: f64) -> f64 {
+    (2f64.powf(cents / 1200.0) - 1.0) * 1_000_000.0
+}
+
+pub fn ppm_to_cents(ppm: f64) -> f64 {
+    1200.0 * (1.0 + ppm / 1_000_000.0).log2()
+}
+
+// xorshift64*, plenty for test noise and no extra dependency
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(now: SystemTime) -> Self {
+        let seed = now
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos() as u64);
+        Self(seed | 1)
+    }
+
+    // uniform in -1..1
+    pub fn signed(&mut self) -> f64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        let bits = self.0.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11;
+        bits as f64 / (1u64 << 52) as f64 - 1.0
+    }
+}
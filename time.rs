@@ -0,0 +1,119 @@
+use std::sync::Arc;
+#[cfg(test)]
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime};
+use crate::audio::AudioClock;
+use crate::clock::{self, Clock, Sleep};
+
+// where the clock thread gets the time and how it waits for it. everything the
+// scheduler decides goes through here, so it can run on a made up clock
+pub trait Time: Send {
+    fn now(&self) -> Instant;
+
+    // the wall clock, for timers and armed starts
+    fn system(&self) -> SystemTime;
+
+    fn wait_until(&self, deadline: Instant, sleep: Sleep);
+
+    fn sleep(&self, duration: Duration) {
+        self.wait_until(self.now() + duration, Sleep::Plain);
+    }
+
+    // nothing to send, until the gui pokes the clock or `timeout` passes
+    fn idle(&self, _: &Clock, timeout: Duration) {
+        self.sleep(timeout);
+    }
+
+    // until the sound card's callback reaches `frame`, false if it hasn't by
+    // `deadline`. without a real sound card there's nothing to wait for
+    fn wait_frame(&self, _: &AudioClock, _: f64, _: Instant) -> bool {
+        false
+    }
+}
+
+pub struct Real;
+
+impl Time for Real {
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+
+    fn system(&self) -> SystemTime {
+        SystemTime::now()
+    }
+
+    fn wait_until(&self, deadline: Instant, sleep: Sleep) {
+        clock::wait_until(deadline, sleep);
+    }
+
+    fn idle(&self, clock: &Clock, timeout: Duration) {
+        clock.idle(timeout);
+    }
+
+    fn wait_frame(&self, audio: &AudioClock, frame: f64, deadline: Instant) -> bool {
+        audio.wait_frame(frame, deadline)
+    }
+}
+
+// time that only moves when waited on, straight to the deadline. hours of ticks run
+// in moments and come out the same every run, for driving the scheduler from tests
+#[cfg(test)]
+pub struct Simulated {
+    start: Instant,
+    wall: SystemTime,
+    elapsed: Mutex<Duration>,
+}
+
+#[cfg(test)]
+impl Simulated {
+    pub fn new(wall: SystemTime) -> Self {
+        Self { start: Instant::now(), wall, elapsed: Mutex::new(Duration::ZERO) }
+    }
+
+    pub fn elapsed(&self) -> Duration {
+        *self.elapsed.lock().unwrap()
+    }
+}
+
+#[cfg(test)]
+impl Time for Simulated {
+    fn now(&self) -> Instant {
+        self.start + self.elapsed()
+    }
+
+    fn system(&self) -> SystemTime {
+        self.wall + self.elapsed()
+    }
+
+    fn wait_until(&self, deadline: Instant, _: Sleep) {
+        let mut elapsed = self.elapsed.lock().unwrap();
+        *elapsed = (*elapsed).max(deadline.saturating_duration_since(self.start));
+    }
+}
+
+// so a test can keep a handle on the time it gave the clock thread
+impl<T: Time + Sync + ?Sized> Time for Arc<T> {
+    fn now(&self) -> Instant {
+        (**self).now()
+    }
+
+    fn system(&self) -> SystemTime {
+        (**self).system()
+    }
+
+    fn wait_until(&self, deadline: Instant, sleep: Sleep) {
+        (**self).wait_until(deadline, sleep);
+    }
+
+    fn sleep(&self, duration: Duration) {
+        (**self).sleep(duration);
+    }
+
+    fn idle(&self, clock: &Clock, timeout: Duration) {
+        (**self).idle(clock, timeout);
+    }
+
+    fn wait_frame(&self, audio: &AudioClock, frame: f64, deadline: Instant) -> bool {
+        (**self).wait_frame(audio, frame, deadline)
+    }
+}
@@ -0,0 +1,61 @@
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use midir::MidiOutputPort;
+use crate::audio::AudioClock;
+use crate::clock::{self, Clock};
+use crate::crash;
+use crate::ltc::Chase;
+use crate::net::Follower;
+use crate::reclock::Pll;
+use crate::session::{self, Kind};
+use crate::time;
+
+const CHECK: Duration = Duration::from_millis(500);
+// the loop comes round at least every half second plus one tick, even idle
+const STALL: Duration = Duration::from_secs(2);
+
+// restarts clock threads that died or stopped coming round their loop, a send
+// blocked in the driver for instance. a wedged thread can't be killed, it's
+// left behind and quits if it ever wakes up. a panicking one only dies on its own
+// in builds that unwind, the release build aborts the whole process instead
+pub fn spawn(
+    clocks: Arc<Mutex<Vec<Arc<Clock>>>>,
+    pll: Arc<Mutex<Pll>>,
+    chase: Arc<Mutex<Chase>>,
+    follower: Arc<Mutex<Follower>>,
+    audio: Arc<AudioClock>,
+    outports: Vec<MidiOutputPort>,
+) {
+    thread::spawn(move || loop {
+        thread::sleep(CHECK);
+        // the gui went down and the clocks are stopping their slaves, leave them stopped
+        if crash::crashed() {
+            continue;
+        }
+        let clocks = clocks.lock().unwrap().clone();
+        for clock in clocks.iter().filter(|c| !c.closed()) {
+            let tempo = clock.tempo();
+            let tick = if tempo > 0.0 { Duration::from_secs_f64(60.0 / (tempo * 24.0)) } else { Duration::ZERO };
+            let why = if clock.finished() {
+                "Clock thread died"
+            } else if clock.since_heartbeat() > STALL + tick {
+                "Clock thread stalled"
+            } else {
+                continue;
+            };
+            eprintln!("{} on {}, restarting it", why, clock.name);
+            session::record(&clock.name, Kind::Error, format!("{}, restarted", why));
+            *clock.incident.lock().unwrap() = Some(format!("{}, restarted", why));
+            clock::spawn(
+                Arc::clone(clock),
+                Arc::clone(&pll),
+                Arc::clone(&chase),
+                Arc::clone(&follower),
+                Arc::clone(&audio),
+                outports.clone(),
+                time::Real,
+            );
+        }
+    });
+}
@@ -0,0 +1,57 @@
+use std::fs;
+use std::path::Path;
+
+// RIFF WAVE, integer PCM or float, mixed down to mono. the only audio format
+// that reads without a decoder crate
+pub fn read(path: &Path) -> Result<(Vec<f32>, f64), String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    if data.len() < 12 || &data[0..4] != b"RIFF" || &data[8..12] != b"WAVE" {
+        return Err("Only WAV files can be analysed".to_string());
+    }
+
+    let mut format = None;
+    let mut samples = None;
+    let mut at = 12;
+    while at + 8 <= data.len() {
+        let id = &data[at..at + 4];
+        let len = u32::from_le_bytes([data[at + 4], data[at + 5], data[at + 6], data[at + 7]]) as usize;
+        let body = &data[at + 8..(at + 8 + len).min(data.len())];
+        match id {
+            b"fmt " if body.len() >= 16 => {
+                let mut tag = u16::from_le_bytes([body[0], body[1]]);
+                // WAVE_FORMAT_EXTENSIBLE keeps the real one in its sub format
+                if tag == 0xFFFE && body.len() >= 26 {
+                    tag = u16::from_le_bytes([body[24], body[25]]);
+                }
+                let channels = u16::from_le_bytes([body[2], body[3]]) as usize;
+                let rate = u32::from_le_bytes([body[4], body[5], body[6], body[7]]) as f64;
+                let bits = u16::from_le_bytes([body[14], body[15]]) as usize;
+                format = Some((tag, channels, rate, bits));
+            }
+            b"data" => samples = Some(body),
+            _ => {}
+        }
+        // chunks are padded to an even length
+        at += 8 + len + len % 2;
+    }
+
+    let (tag, channels, rate, bits) = format.ok_or("WAV file has no format chunk")?;
+    let samples = samples.ok_or("WAV file has no data chunk")?;
+    if channels == 0 || rate <= 0.0 {
+        return Err("WAV file has no channels".to_string());
+    }
+    let width = bits / 8;
+    let decode: fn(&[u8]) -> f32 = match (tag, bits) {
+        (1, 8) => |b| (b[0] as f32 - 128.0) / 128.0,
+        (1, 16) => |b| i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+        (1, 24) => |b| i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2147483648.0,
+        (1, 32) => |b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648.0,
+        (3, 32) => |b| f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        _ => return Err(format!("Unsupported WAV encoding ({} bit, format {})", bits, tag)),
+    };
+    let mono = samples
+        .chunks_exact(width * channels)
+        .map(|frame| frame.chunks_exact(width).map(decode).sum::<f32>() / channels as f32)
+        .collect();
+    Ok((mono, rate))
+}